@@ -1,8 +1,42 @@
-use crate::actor::Actor;
+use crate::actor::{Actor, Cause, article};
 use crate::level::Level;
-use crate::util::Point;
+use crate::map::{TileKind, TrapKind};
+use crate::item::{self, Item, Effect, ItemType};
+use crate::container::{self, Container};
+
+use rand::Rng;
+use crate::util::{Point, capitalize, uncapitalize};
 use crate::action::Direction::NoDir;
 
+/// Damage dealt to an `Entity` stepping into lava
+const LAVA_DAMAGE: i32 = 5;
+/// Damage dealt by a dart trap
+const DART_DAMAGE: i32 = 3;
+/// Damage dealt by falling into a pit
+const PIT_DAMAGE: i32 = 2;
+/// The chance of finding each adjacent hidden trap when searching
+const SEARCH_CHANCE: f64 = 1.0 / 3.0;
+/// The chance of finding each adjacent hidden trap when searching with a ring of searching on
+const RING_SEARCH_CHANCE: f64 = 2.0 / 3.0;
+/// How much health a potion of healing restores
+const HEAL_AMOUNT: i32 = 8;
+/// How much health a potion of sickness takes away, though it never takes the last of it
+const SICKNESS_DAMAGE: i32 = 5;
+/// Damage dealt by a wand of striking
+const STRIKING_DAMAGE: i32 = 6;
+/// How far away the player's footsteps can be heard
+const WALK_NOISE: i32 = 4;
+/// How far away an alarm trap can be heard
+const ALARM_NOISE: i32 = 40;
+/// How far away the sounds of a fight can be heard
+const FIGHT_NOISE: i32 = 8;
+/// How far away a kick can be heard
+const KICK_NOISE: i32 = 10;
+/// The chance of a kick bursting a locked chest open
+const KICK_OPEN_CHANCE: f64 = 1.0 / 3.0;
+/// What's felt when a teleport finds nowhere to go
+const DISORIENTATION: &str = "You feel a momentary disorientation";
+
 pub trait Action {
     fn perform(&self, level: &mut Level) -> ActionResult;
 }
@@ -12,6 +46,7 @@ pub struct ActionResult {
     pub alternate: Option<Box<dyn Action + 'static>>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Direction {
     N,
     S,
@@ -24,31 +59,119 @@ pub enum Direction {
     NoDir,
 }
 
+impl Direction {
+    /// The offset of a single step in this direction
+    pub fn offset(&self) -> Point {
+        use crate::action::Direction::*;
+        let (x, y) = match self {
+            N => (0, -1),
+            S => (0, 1),
+            W => (-1, 0),
+            E => (1, 0),
+            NW => (-1, -1),
+            NE => (1, -1),
+            SW => (-1, 1),
+            SE => (1, 1),
+            NoDir => (0, 0),
+        };
+
+        Point { x, y }
+    }
+}
+
 pub struct PickupAction {
-    pub target: usize
+    pub target: usize,
+    /// Which stacks of the pile to take, by their place in it. All of them if `None`.
+    pub choice: Option<Vec<usize>>,
 }
 
 impl PickupAction {
-    pub fn new(target: usize) -> PickupAction { PickupAction { target } }
+    pub fn new(target: usize) -> PickupAction { PickupAction { target, choice: None } }
 }
 
 impl Action for PickupAction {
     fn perform(&self, level: &mut Level) -> ActionResult {
         let pos = level.entities[self.target].as_ref().unwrap().position;
 
-        let maybe_items = level.items.remove(&pos);
-        
-        if let Some(items) = maybe_items {
-            let inventory = &mut level.entities[self.target].as_mut().unwrap().inventory;
-            inventory.extend(items);
-            for item in inventory {
-                println!("{}", item.name);
+        let mut pile = match level.items.remove(&pos) {
+            Some(pile) => pile,
+            None => return ActionResult { success: false, alternate: None },
+        };
+
+        // Taken from the back so the earlier places in the pile stay put
+        let count = pile.len();
+        let mut chosen = self.choice.clone().unwrap_or((0..count).collect());
+        chosen.retain(|&i| i < count);
+        chosen.sort();
+        chosen.dedup();
+
+        let mut taken = vec![];
+        for i in chosen.into_iter().rev() {
+            taken.insert(0, pile.remove(i));
+        }
+
+        // The shop's stock has to be paid for, as long as the shopkeeper is there to ask
+        let owed = self.target == 0 && level.shop_open();
+
+        let entity = level.entities[self.target].as_mut().unwrap();
+        let before = entity.encumbrance();
+
+        // Only as much is taken as can be carried without being overloaded
+        let mut picked = vec![];
+        let mut left = vec![];
+        let mut fixed = vec![];
+        for mut item in taken {
+            // Chests stay where they are, so their loot has to be taken out of them
+            if item.name == item::CHEST {
+                fixed.push(item.describe_to(&level.discoveries));
+                left.push(item);
+                continue
             }
-        } else {
-            return ActionResult { success: false, alternate: None }
+
+            let fits = if item.weight == 0 { item.quantity } else { (entity.spare_capacity() / item.weight).min(item.quantity) };
+            if fits < item.quantity {
+                if fits > 0 {
+                    let mut part = item.split(fits);
+                    part.claim(owed);
+                    picked.push(part.describe_to(&level.discoveries));
+                    entity.stow(part);
+                }
+                left.push(item);
+                continue
+            }
+
+            item.claim(owed);
+            picked.push(item.describe_to(&level.discoveries));
+            entity.stow(item);
         }
-        
-        ActionResult { success: true, alternate: None }
+
+        let player = entity.player;
+        let after = entity.encumbrance();
+
+        let names = left.iter().filter(|item| item.name != item::CHEST).map(|item| item.describe_to(&level.discoveries)).collect::<Vec<_>>();
+        for item in left {
+            item::add_to_pile(&mut pile, item);
+        }
+        if !pile.is_empty() {
+            level.items.insert(pos, pile);
+        }
+
+        if player {
+            if !picked.is_empty() {
+                level.messages.push(format!("You pick up {}", picked.join(", ")));
+            }
+            if !names.is_empty() {
+                level.messages.push(format!("You can't carry {} as well", names.join(", ")));
+            }
+            if !fixed.is_empty() {
+                level.messages.push(format!("You can't lift {}", fixed.join(", ")));
+            }
+            if after > before {
+                level.messages.push(format!("You are {} by your load", after.name()));
+            }
+        }
+
+        ActionResult { success: !picked.is_empty(), alternate: None }
     }
 }
 
@@ -67,39 +190,552 @@ impl WalkAction {
 
 impl Action for WalkAction {
     fn perform(&self, level: &mut Level) -> ActionResult {
-        use crate::action::Direction::*;
-        let actor = &mut level.entities[self.target];
-
-        let position = actor.as_ref().unwrap().get_position();
+        let position = level.entities[self.target].as_ref().unwrap().get_position();
 
         let new_position = if let Some(offset) = self.offset {
             position.offset(offset.x, offset.y)
         } else if let Some(point) = self.point {
             point
         } else {
-            match self.direction {
-                N => position.offset_y(-1),
-                S => position.offset_y(1),
-                W => position.offset_x(-1),
-                E => position.offset_x(1),
-                NW => position.offset(-1, -1),
-                NE => position.offset(1, -1),
-                SW => position.offset(-1, 1),
-                SE => position.offset(1, 1),
-                NoDir => position,
-            }
+            let offset = self.direction.offset();
+            position.offset(offset.x, offset.y)
         };
 
+        if level.map_component.is_occupied(new_position.x, new_position.y) {
+            if let Some(victim) = level.entity_at(new_position) {
+                if level.entities[self.target].as_ref().unwrap().is_enemy_of(level.entities[victim].as_ref().unwrap()) {
+                    return ActionResult { success: false, alternate: Some(box AttackAction { target: self.target, victim }) }
+                }
 
+                // Bumping into a shopkeeper is how the player trades with them, which takes no time
+                if self.target == 0 && level.shopkeeper() == Some(victim) {
+                    level.trading = true;
+                    return ActionResult { success: false, alternate: None }
+                }
+            }
+        }
+
+        let actor = &mut level.entities[self.target];
         if !level.map_component.is_blocked(new_position.x, new_position.y) && !level.map_component.is_occupied(new_position.x, new_position.y){
 
+            let player = actor.as_ref().unwrap().player;
+
+            // A heavy load makes steps take longer, or stops them altogether
+            let entity = actor.as_mut().unwrap();
+            match entity.encumbrance().extra_rounds(entity.steps) {
+                Some(rounds) => {
+                    entity.steps += 1;
+                    entity.delay += rounds;
+                }
+                None => {
+                    if player {
+                        level.messages.push("You are carrying too much to move".to_string());
+                    }
+                    return ActionResult { success: false, alternate: None }
+                }
+            }
+
             actor.as_mut().unwrap().set_position(new_position);
             let map = level.map_component.get_map_mut();
             map[new_position.x as usize][new_position.y as usize].occupied = true;
             map[position.x as usize][position.y as usize].occupied = false;
+
+            if player {
+                level.make_noise(new_position, WALK_NOISE);
+            }
+
+            enter_terrain(level, self.target, new_position);
+            return ActionResult { success: true, alternate: None }
+        }
+
+        ActionResult { success: true, alternate: None }
+    }
+}
+
+/// Applies the effects of the terrain at `position` to the `Entity`
+/// at index `target`, which has just stepped onto it.
+fn enter_terrain(level: &mut Level, target: usize, position: Point) {
+    let kind = level.map_component.get_map()[position.x as usize][position.y as usize].kind;
+    let entity = level.entities[target].as_mut().unwrap();
+
+    let message = match kind {
+        TileKind::Lava => {
+            entity.hurt(LAVA_DAMAGE, Cause::Lava);
+            if entity.player {
+                Some("You are burned by the lava!".to_string())
+            } else {
+                Some(format!("The {} is burned by the lava!", entity.name))
+            }
+        }
+        TileKind::Water | TileKind::DeepWater => {
+            let mut doused = false;
+            for item in entity.inventory.iter_mut().filter(|i| i.lit) {
+                item.lit = false;
+                doused = true;
+            }
+
+            if doused && entity.player {
+                Some("The water puts out your torch".to_string())
+            } else {
+                None
+            }
+        }
+        TileKind::Trap(trap) => {
+            level.reveal(position);
+            spring_trap(level, target, trap);
+            None
+        }
+        _ => None
+    };
+
+    if let Some(m) = message {
+        level.log(m);
+    }
+}
+
+/// Triggers a trap under the `Entity` at index `target`
+fn spring_trap(level: &mut Level, target: usize, trap: TrapKind) {
+    let entity = level.entities[target].as_mut().unwrap();
+    let message = if entity.player {
+        format!("You set off {}!", trap.name())
+    } else {
+        format!("The {} sets off {}!", entity.name, trap.name())
+    };
+    level.log(message);
+
+    match trap {
+        TrapKind::Dart => level.entities[target].as_mut().unwrap().hurt(DART_DAMAGE, Cause::Trap(trap)),
+        TrapKind::Pit => level.entities[target].as_mut().unwrap().hurt(PIT_DAMAGE, Cause::Trap(trap)),
+        TrapKind::Teleport => match level.random_free_point() {
+            Some(new) => level.teleport(target, new),
+            None => if level.entities[target].as_ref().unwrap().player {
+                level.log(DISORIENTATION.to_string());
+            }
+        }
+        TrapKind::Alarm => {
+            level.log("A loud alarm rings out!".to_string());
+            let position = level.entities[target].as_ref().unwrap().get_position();
+            level.make_noise(position, ALARM_NOISE);
+            level.wake_all();
+        }
+    }
+}
+
+/// Hits another `Entity` in melee
+pub struct AttackAction {
+    pub target: usize,
+    pub victim: usize,
+}
+
+impl Action for AttackAction {
+    fn perform(&self, level: &mut Level) -> ActionResult {
+        let attacker = level.entities[self.target].as_ref().unwrap();
+        let (damage, position) = (attacker.damage, attacker.get_position());
+        let (attacker_name, cause) = (attacker.describe(), attacker.as_cause());
+
+        let victim = level.entities[self.victim].as_mut().unwrap();
+        victim.hurt(damage, cause);
+        let victim_name = victim.describe();
+
+        let verb = if self.target == 0 { "hit" } else { "hits" };
+        level.log(capitalize(&format!("{} {} {}", attacker_name, verb, victim_name)));
+        level.make_noise(position, FIGHT_NOISE);
+
+        ActionResult { success: true, alternate: None }
+    }
+}
+
+/// Shoots a projectile at a point, hitting the first `Entity` in its way
+pub struct FireAction {
+    pub target: usize,
+    pub at: Point,
+    pub damage: i32,
+}
+
+impl Action for FireAction {
+    fn perform(&self, level: &mut Level) -> ActionResult {
+        let shooter = level.entities[self.target].as_ref().unwrap();
+        let (position, shooter_name, cause) = (shooter.get_position(), shooter.describe(), shooter.as_cause());
+
+        let mut message = capitalize(&format!("{} shoots and misses", shooter_name));
+        for point in position.line_to(&self.at).into_iter().skip(1) {
+            if level.map_component.is_blocked(point.x, point.y) {
+                break
+            }
+
+            if let Some(victim) = level.entity_at(point) {
+                let victim = level.entities[victim].as_mut().unwrap();
+                victim.hurt(self.damage, cause.clone());
+                message = capitalize(&format!("{} shoots {}", shooter_name, victim.describe()));
+                break
+            }
+        }
+
+        level.log(message);
+        level.make_noise(position, FIGHT_NOISE);
+
+        ActionResult { success: true, alternate: None }
+    }
+}
+
+/// Uses an item from the `Entity`'s pack: drinks a potion, reads a scroll,
+/// puts on or takes off a ring, or zaps a wand at a point. Items that give
+/// themselves away when used are identified.
+pub struct UseAction {
+    pub target: usize,
+    /// The item's place in the pack
+    pub item: usize,
+    /// Where a wand is zapped
+    pub at: Option<Point>,
+}
+
+impl Action for UseAction {
+    fn perform(&self, level: &mut Level) -> ActionResult {
+        let entity = level.entities[self.target].as_mut().unwrap();
+        let (name, item_type, effect, known_as) = match entity.inventory.get(self.item).and_then(|item| Some((item, item.effect()?))) {
+            Some((item, effect)) => (item.name.clone(), item.item_type, effect, uncapitalize(&level.discoveries.name_of(item))),
+            None => return ActionResult { success: false, alternate: None },
+        };
+        if item_type == ItemType::WAND && self.at.is_none() {
+            return ActionResult { success: false, alternate: None }
+        }
+
+        // Potions and scrolls are used up, and rings go on or come off
+        let message = match item_type {
+            ItemType::POTION | ItemType::SCROLL => {
+                if entity.inventory[self.item].quantity > 1 {
+                    entity.inventory[self.item].quantity -= 1;
+                } else {
+                    entity.inventory.remove(self.item);
+                }
+                format!("You {} the {}", if item_type == ItemType::POTION { "drink" } else { "read" }, known_as)
+            }
+            ItemType::RING => {
+                let ring = &mut entity.inventory[self.item];
+                ring.worn = !ring.worn;
+                if !ring.worn {
+                    level.log(format!("You take off the {}", known_as));
+                    return ActionResult { success: true, alternate: None }
+                }
+                format!("You put on the {}", known_as)
+            }
+            _ => format!("You zap the {}", known_as),
+        };
+        level.log(message);
+
+        if self.apply(level, effect, &name) && level.discoveries.identify(&name) {
+            let used_up = item_type == ItemType::POTION || item_type == ItemType::SCROLL;
+            let name = uncapitalize(&name);
+            level.log(format!("{} {} {}", if used_up { "That was" } else { "It's" }, article(&name), name));
+        }
+
+        ActionResult { success: true, alternate: None }
+    }
+}
+
+impl UseAction {
+    /// Carries out the effect of the item called `name`, returning whether it gave away what the item is
+    fn apply(&self, level: &mut Level, effect: Effect, name: &str) -> bool {
+        match effect {
+            Effect::Heal => {
+                let entity = level.entities[self.target].as_mut().unwrap();
+                entity.health = (entity.health + HEAL_AMOUNT).min(entity.max_health);
+                level.log("You feel better".to_string());
+                true
+            }
+            Effect::GainStrength => {
+                level.entities[self.target].as_mut().unwrap().strength += 1;
+                level.log("You feel strong!".to_string());
+                true
+            }
+            Effect::Sickness => {
+                let entity = level.entities[self.target].as_mut().unwrap();
+                entity.health = (entity.health - SICKNESS_DAMAGE).max(1);
+                level.log("You feel very sick".to_string());
+                true
+            }
+            Effect::Identify => {
+                // The scroll gives itself away before anything else
+                if level.discoveries.identify(name) {
+                    let name = uncapitalize(name);
+                    level.log(format!("This is {} {}", article(&name), name));
+                }
+
+                let mut unknown = level.entities[self.target].as_ref().unwrap().inventory.iter()
+                    .map(|item| item.name.clone())
+                    .filter(|name| !level.discoveries.is_known(name))
+                    .collect::<Vec<_>>();
+                unknown.sort();
+                unknown.dedup();
+
+                if unknown.is_empty() {
+                    level.log("You have nothing left to identify".to_string());
+                } else {
+                    let kind = unknown[level.rng.combat.gen_range(0, unknown.len())].clone();
+                    let appearance = level.discoveries.appearance(&kind).unwrap().to_string();
+                    level.discoveries.identify(&kind);
+
+                    let kind = uncapitalize(&kind);
+                    level.log(format!("You learn that the {} is {} {}", appearance, article(&kind), kind));
+                }
+                false
+            }
+            Effect::Teleport => match level.random_free_point() {
+                Some(to) => {
+                    level.teleport(self.target, to);
+                    level.log("You feel a wrenching sensation".to_string());
+                    true
+                }
+                None => {
+                    level.log(DISORIENTATION.to_string());
+                    false
+                }
+            }
+            Effect::MagicMapping => {
+                for column in level.map_component.get_map_mut().iter_mut() {
+                    for tile in column.iter_mut() {
+                        tile.explored = true;
+                    }
+                }
+                level.log("A map coalesces in your mind!".to_string());
+                true
+            }
+            Effect::Strength(n) => {
+                level.log(if n > 0 { "You feel stronger" } else { "You feel weaker" }.to_string());
+                true
+            }
+            // Nothing shows until a trap turns up
+            Effect::Searching => false,
+            Effect::Striking | Effect::TeleportOther => {
+                let position = level.entities[self.target].as_ref().unwrap().get_position();
+                let victim = position.line_to(&self.at.unwrap()).into_iter().skip(1)
+                    .take_while(|point| !level.map_component.is_blocked(point.x, point.y))
+                    .find_map(|point| level.entity_at(point));
+
+                let victim = match victim {
+                    Some(victim) => victim,
+                    None => {
+                        level.log("Nothing seems to happen".to_string());
+                        return false
+                    }
+                };
+
+                let cause = level.entities[self.target].as_ref().unwrap().as_cause();
+                let victim_name = level.entities[victim].as_ref().unwrap().describe();
+                if effect == Effect::Striking {
+                    level.entities[victim].as_mut().unwrap().hurt(STRIKING_DAMAGE, cause);
+                    level.log(format!("The wand hits {}", victim_name));
+                } else {
+                    match level.random_free_point() {
+                        Some(to) => {
+                            level.teleport(victim, to);
+                            level.log(capitalize(&format!("{} vanishes!", victim_name)));
+                        }
+                        None => {
+                            level.log(DISORIENTATION.to_string());
+                            return false
+                        }
+                    }
+                }
+                true
+            }
+        }
+    }
+}
+
+/// Takes things out of a container and puts things from the pack into it,
+/// or unlocks it with a key if it's locked
+pub struct OpenAction {
+    pub target: usize,
+    pub container: Container,
+    /// What to take out, by their place in the container
+    pub take: Vec<usize>,
+    /// What to put in, by their place in the pack
+    pub put: Vec<usize>,
+}
+
+impl OpenAction {
+    fn unlock(&self, level: &mut Level, container: &mut Item, name: &str) -> bool {
+        let entity = level.entities[self.target].as_ref().unwrap();
+        if !entity.inventory.iter().any(|item| item.name == item::KEY) {
+            level.log(format!("{} is locked", capitalize(name)));
+            return false
+        }
+
+        container.locked = false;
+        level.log(format!("You unlock {} with your key", name));
+        true
+    }
+
+    /// Swaps things between the container and the pack, returning where the container goes back in the pack
+    fn transfer(&self, level: &mut Level, container: &mut Item, name: &str) -> (bool, Container) {
+        // The shop's stock has to be paid for, as long as the shopkeeper is there to ask
+        let owed = self.target == 0 && level.shop_open();
+
+        let entity = level.entities[self.target].as_mut().unwrap();
+        let discoveries = &level.discoveries;
+        let before = entity.encumbrance();
+
+        // The places in the pack were chosen with the container still in it
+        let mut put = self.put.iter()
+            .map(|&i| match self.container { Container::Pack(at) if i > at => i - 1, _ => i })
+            .filter(|&i| i < entity.inventory.len())
+            .collect::<Vec<_>>();
+        put.sort();
+        put.dedup();
+
+        let mut take = self.take.iter().cloned().filter(|&i| i < container.contents.len()).collect::<Vec<_>>();
+        take.sort();
+        take.dedup();
+
+        // Taken from the back so the earlier places stay put
+        let mut taken = vec![];
+        for i in take.into_iter().rev() {
+            taken.insert(0, container.contents.remove(i));
+        }
+        let mut stowed = vec![];
+        for &i in put.iter().rev() {
+            stowed.insert(0, entity.inventory.remove(i));
+        }
+
+        let put_in = stowed.iter().map(|item| item.describe_to(discoveries)).collect::<Vec<_>>();
+        for item in stowed {
+            item::add_to_pile(&mut container.contents, item);
+        }
+
+        // Only what can be carried comes out of a container on the floor. Taking from a bag weighs nothing more.
+        let mut took_out = vec![];
+        let mut left = vec![];
+        for mut item in taken {
+            if let Container::Floor(_) = self.container {
+                if item.weight() > entity.spare_capacity() {
+                    left.push(item.describe_to(discoveries));
+                    item::add_to_pile(&mut container.contents, item);
+                    continue
+                }
+            }
+            item.claim(owed);
+            took_out.push(item.describe_to(discoveries));
+            entity.stow(item);
+        }
+
+        let after = entity.encumbrance();
+        let back = match self.container {
+            Container::Pack(at) => Container::Pack(at - put.iter().filter(|&&i| i < at).count()),
+            floor => floor,
+        };
+
+        if !took_out.is_empty() {
+            level.log(format!("You take {} out of {}", took_out.join(", "), name));
+        }
+        if !left.is_empty() {
+            level.log(format!("You can't carry {} as well", left.join(", ")));
+        }
+        if !put_in.is_empty() {
+            level.log(format!("You put {} into {}", put_in.join(", "), name));
+        }
+        if after > before {
+            level.log(format!("You are {} by your load", after.name()));
+        }
+
+        (!took_out.is_empty() || !put_in.is_empty(), back)
+    }
+}
+
+impl Action for OpenAction {
+    fn perform(&self, level: &mut Level) -> ActionResult {
+        let mut opened = match container::lift(level, self.target, self.container) {
+            Some(opened) => opened,
+            None => return ActionResult { success: false, alternate: None },
+        };
+        let name = container::name(&opened, &level.discoveries);
+
+        let (success, back) = if opened.locked {
+            (self.unlock(level, &mut opened, &name), self.container)
+        } else {
+            self.transfer(level, &mut opened, &name)
+        };
+        container::put_back(level, self.target, back, opened);
+
+        ActionResult { success, alternate: None }
+    }
+}
+
+/// Kicks whatever is next to the `Entity` in `direction`, which may burst a locked chest open
+pub struct KickAction {
+    pub target: usize,
+    pub direction: Direction,
+}
+
+impl Action for KickAction {
+    fn perform(&self, level: &mut Level) -> ActionResult {
+        let offset = self.direction.offset();
+        let at = level.entities[self.target].as_ref().unwrap().get_position().offset(offset.x, offset.y);
+
+        if let Some(victim) = level.entity_at(at) {
+            if level.entities[self.target].as_ref().unwrap().is_enemy_of(level.entities[victim].as_ref().unwrap()) {
+                return ActionResult { success: false, alternate: Some(box AttackAction { target: self.target, victim }) }
+            }
+
+            let name = level.entities[victim].as_ref().unwrap().describe();
+            level.log(format!("You'd rather not kick {}", name));
+            return ActionResult { success: false, alternate: None }
+        }
+
+        if !level.map_component.contains(at.x, at.y) || level.map_component.is_blocked(at.x, at.y) {
+            level.log("Ouch! That hurts!".to_string());
             return ActionResult { success: true, alternate: None }
         }
 
+        let chest = level.items.get(&at).and_then(|pile| pile.iter().position(|item| item.is_container()));
+        let chest = match chest {
+            Some(chest) => chest,
+            None => {
+                level.log("You kick at empty space".to_string());
+                return ActionResult { success: true, alternate: None }
+            }
+        };
+
+        level.make_noise(at, KICK_NOISE);
+        let locked = level.items[&at][chest].locked;
+        if locked && level.rng.combat.gen_bool(KICK_OPEN_CHANCE) {
+            let item = &mut level.items.get_mut(&at).unwrap()[chest];
+            item.locked = false;
+            let name = container::name(item, &level.discoveries);
+            level.log(format!("WHAMM! The lid of {} bursts open!", name));
+        } else {
+            level.log("WHAMM!".to_string());
+        }
+
+        ActionResult { success: true, alternate: None }
+    }
+}
+
+/// Looks for hidden traps around the `Entity`
+pub struct SearchAction {
+    pub target: usize
+}
+
+impl Action for SearchAction {
+    fn perform(&self, level: &mut Level) -> ActionResult {
+        let searcher = level.entities[self.target].as_ref().unwrap();
+        let position = searcher.get_position();
+        let chance = if searcher.is_wearing(Effect::Searching) { RING_SEARCH_CHANCE } else { SEARCH_CHANCE };
+
+        for x in (position.x - 1)..(position.x + 2) {
+            for y in (position.y - 1)..(position.y + 2) {
+                if !level.map_component.contains(x, y) || !level.rng.combat.gen_bool(chance) {
+                    continue
+                }
+
+                let tile = level.map_component.get_map()[x as usize][y as usize];
+                if let (true, TileKind::Trap(trap)) = (tile.hidden, tile.kind) {
+                    level.reveal(Point { x, y });
+                    level.log(format!("You find {}", trap.name()));
+                }
+            }
+        }
+
         ActionResult { success: true, alternate: None }
     }
 }
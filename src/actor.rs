@@ -1,12 +1,19 @@
 use crate::util::Point;
 use crate::rendering::RenderingComponent;
-use crate::brain::{AggroBrainComponent, BrainComponent};
+use crate::brain::BrainComponent;
 use crate::action::Action;
-use crate::item::Item;
+use crate::item::{self, Item, ItemType, Effect};
 use crate::level::Level;
 use crate::brain::PlayerBrainComponent;
 use crate::brain::NoBrainComponent;
+use crate::map::TrapKind;
 
+/// How much anything can carry with no strength at all
+const BASE_CAPACITY: u32 = 50;
+/// How much more can be carried for each point of strength
+const CAPACITY_PER_STRENGTH: u32 = 25;
+/// The most anything can carry, however strong
+const MAX_CAPACITY: u32 = 1000;
 
 pub trait Actor {
     fn set_position(&mut self, new_pos: Point);
@@ -16,6 +23,94 @@ pub trait Actor {
     fn get_display_char(&self) -> char;
 }
 
+/// How aware a monster is of the player
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alertness {
+    /// Dead to the world until something wakes it
+    Asleep,
+    /// Awake and roaming, but not after anyone in particular
+    Wandering,
+    /// Actively going after the player
+    Hunting,
+}
+
+/// How weighed down an `Entity` is by what it's carrying
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Encumbrance {
+    Unencumbered,
+    /// Carrying up to half as much again as it can: every third step takes an extra round
+    Burdened,
+    /// Carrying up to twice as much as it can: every step takes an extra round
+    Stressed,
+    /// Carrying more than that: too weighed down to move
+    Overloaded,
+}
+
+impl Encumbrance {
+    /// How weighed down something carrying `weight` is, when it can carry `capacity`
+    pub fn for_weight(weight: u32, capacity: u32) -> Encumbrance {
+        if weight <= capacity {
+            Encumbrance::Unencumbered
+        } else if weight * 2 <= capacity * 3 {
+            Encumbrance::Burdened
+        } else if weight <= capacity * 2 {
+            Encumbrance::Stressed
+        } else {
+            Encumbrance::Overloaded
+        }
+    }
+
+    /// How many extra rounds the step after `steps` earlier ones takes, or `None` if no step can be taken
+    pub fn extra_rounds(&self, steps: u32) -> Option<u32> {
+        match self {
+            Encumbrance::Unencumbered => Some(0),
+            Encumbrance::Burdened => Some(if steps % 3 == 2 { 1 } else { 0 }),
+            Encumbrance::Stressed => Some(1),
+            Encumbrance::Overloaded => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encumbrance::Unencumbered => "unencumbered",
+            Encumbrance::Burdened => "burdened",
+            Encumbrance::Stressed => "stressed",
+            Encumbrance::Overloaded => "overloaded",
+        }
+    }
+}
+
+/// What last hurt an `Entity`, and so what killed it if it died
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cause {
+    /// Hit or shot by the player
+    Player,
+    /// Hit or shot by the monster with this name
+    Monster(String),
+    Lava,
+    Trap(TrapKind),
+}
+
+impl Cause {
+    /// How a death by this cause reads, e.g. "killed by a kobold"
+    pub fn describe(&self) -> String {
+        match self {
+            Cause::Player => "killed by you".to_string(),
+            Cause::Monster(name) => format!("killed by {} {}", article(name), name),
+            Cause::Lava => "burned to death in lava".to_string(),
+            Cause::Trap(trap) => format!("killed by {}", trap.name()),
+        }
+    }
+}
+
+/// The indefinite article that goes before `name`
+pub fn article(name: &str) -> &'static str {
+    match name.chars().next() {
+        Some(c) if "aeiouAEIOU".contains(c) => "an",
+        _ => "a",
+    }
+}
+
 /// Struct representing both passive and aggressive mobs
 pub struct Entity {
     /// The current position of the `Entity`
@@ -24,12 +119,33 @@ pub struct Entity {
     pub last_position: Point,
     /// The health of the `Entity`
     pub health: i32,
+    /// The health the `Entity` started out with
+    pub max_health: i32,
+    /// How much damage the `Entity` deals in melee
+    pub damage: i32,
+    /// How strong the `Entity` is, which decides how much it can carry
+    pub strength: u32,
     /// The character to render the `Entity` as
     pub display_char: char,
     /// The movement component dictating the way the `Entity` moves
     pub brain_component: Box<dyn BrainComponent + 'static>,
     /// Whether the mob is aggro'd to the player
     pub is_hostile: bool,
+    /// The name used to refer to the `Entity` in messages
+    pub name: String,
+    /// How aware the `Entity` is of the player
+    pub alertness: Alertness,
+
+    /// What last hurt the `Entity`
+    pub last_hurt_by: Option<Cause>,
+    /// Experience earned from kills
+    pub xp: u32,
+    /// How many gold pieces the `Entity` has, which are kept apart from the rest of its pack
+    pub gold: u32,
+    /// How many steps the `Entity` has taken, which spaces out the extra rounds a burden costs
+    pub steps: u32,
+    /// How many rounds the `Entity` must sit out before it acts again, such as after a step under a heavy load
+    pub delay: u32,
 
     pub player: bool,
     pub inventory: Vec<Item>,
@@ -55,16 +171,26 @@ impl Actor for Entity {
 
 impl Entity {
     /// Creates a new actor, with all fields given as parameters
-    pub fn new(x: i32, y: i32, health: i32, dc: char, bc: Box<dyn BrainComponent>, is_hostile: bool) -> Entity {
+    pub fn new(name: &str, x: i32, y: i32, health: i32, damage: i32, dc: char, bc: Box<dyn BrainComponent>, is_hostile: bool) -> Entity {
         Entity {
             health,
+            damage,
+            max_health: health,
             is_hostile,
+            name: name.to_string(),
+            alertness: Alertness::Wandering,
             position: Point { x, y },
             last_position: Point { x: -1, y: -1 },
             display_char: dc,
             brain_component: bc,
             inventory: vec![],
             wielded: None,
+            last_hurt_by: None,
+            xp: 0,
+            gold: 0,
+            strength: 10,
+            steps: 0,
+            delay: 0,
             player: false,
         }
     }
@@ -87,21 +213,122 @@ impl Entity {
         self.health
     }
 
-    /// Creates an `Entity` with traits of a kobold
-    pub fn kobold(x: i32, y: i32) -> Entity {
-        let mc: Box<AggroBrainComponent> = box AggroBrainComponent::new();
-        Entity::new(x, y, 12, 'k', mc, true)
+    /// Whether the `Entity` has run out of health
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0
+    }
+
+    /// Takes `damage` from the `Entity`'s health, remembering what dealt it
+    pub fn hurt(&mut self, damage: i32, cause: Cause) {
+        self.health -= damage;
+        self.last_hurt_by = Some(cause);
+    }
+
+    /// What the `Entity` counts as when it hurts something else
+    pub fn as_cause(&self) -> Cause {
+        if self.player {
+            Cause::Player
+        } else {
+            Cause::Monster(self.name.clone())
+        }
+    }
+
+    /// Whether the `Entity` is wearing a ring with the given effect
+    pub fn is_wearing(&self, effect: Effect) -> bool {
+        self.inventory.iter().any(|item| item.worn && item.effect() == Some(effect))
+    }
+
+    /// How strong the `Entity` is with the rings it's wearing
+    pub fn total_strength(&self) -> u32 {
+        let rings = self.inventory.iter().filter(|item| item.worn).filter_map(|item| match item.effect() {
+            Some(Effect::Strength(n)) => Some(n),
+            _ => None,
+        });
+        (self.strength as i32 + rings.sum::<i32>()).max(0) as u32
+    }
+
+    /// How much the `Entity` can carry before it's weighed down
+    pub fn capacity(&self) -> u32 {
+        (BASE_CAPACITY + CAPACITY_PER_STRENGTH * self.total_strength()).min(MAX_CAPACITY)
+    }
+
+    /// How much everything the `Entity` is carrying weighs, gold included
+    pub fn carried_weight(&self) -> u32 {
+        self.inventory.iter().map(|item| item.weight()).sum::<u32>() + self.gold * item::COIN_WEIGHT
+    }
+
+    /// Puts an item in the `Entity`'s pack, or in its purse if it's gold
+    pub fn stow(&mut self, item: Item) {
+        if item.item_type == ItemType::COIN {
+            self.gold += item.quantity;
+        } else {
+            item::add_to_pile(&mut self.inventory, item);
+        }
+    }
+
+    /// How much the `Entity` owes for what it's picked up in a shop
+    pub fn bill(&self) -> u32 {
+        self.inventory.iter().map(|item| item.bill()).sum()
+    }
+
+    /// Makes everything in the pack the `Entity`'s own, whether it was paid for or not
+    pub fn clear_unpaid(&mut self) {
+        let pack = std::mem::replace(&mut self.inventory, vec![]);
+        for mut item in pack {
+            item.unpaid = false;
+            item.contents.iter_mut().for_each(|i| i.unpaid = false);
+            item::add_to_pile(&mut self.inventory, item);
+        }
+    }
+
+    /// How weighed down the `Entity` is by what it's carrying
+    pub fn encumbrance(&self) -> Encumbrance {
+        Encumbrance::for_weight(self.carried_weight(), self.capacity())
+    }
+
+    /// How much more the `Entity` can pick up before it's overloaded
+    pub fn spare_capacity(&self) -> u32 {
+        (self.capacity() * 2).saturating_sub(self.carried_weight())
+    }
+
+    /// Whether the `Entity` has lost more than half of its health
+    pub fn is_hurt(&self) -> bool {
+        self.health * 2 < self.max_health
+    }
+
+    /// How the `Entity` is referred to in the middle of a message, e.g. "the kobold"
+    pub fn describe(&self) -> String {
+        if self.player {
+            self.name.clone()
+        } else {
+            format!("the {}", self.name)
+        }
+    }
+
+    /// Whether the `Entity` would attack `other` when trying to walk into it
+    pub fn is_enemy_of(&self, other: &Entity) -> bool {
+        (self.player && other.is_hostile) || (self.is_hostile && other.player)
     }
 
     pub fn player(start_pos: Point) -> Entity {
         Entity {
             position: start_pos,
             is_hostile: false,
+            name: "you".to_string(),
+            alertness: Alertness::Hunting,
             last_position: Point { x: -1, y: -1 },
             health: 15,
+            max_health: 15,
+            damage: 4,
             display_char: '@',
             inventory: vec![],
             wielded: None,
+            last_hurt_by: None,
+            xp: 0,
+            gold: 0,
+            strength: 12,
+            steps: 0,
+            delay: 0,
             brain_component: box PlayerBrainComponent::new(),
             player: true,
         }
@@ -2,12 +2,228 @@ use crate::util::Point;
 use crate::map::MapComponent;
 
 use pathfinding::prelude::astar;
+use hashbrown::HashSet;
 
-pub const SHOW_AI: bool = true;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// The value of tiles in a `DistanceMap` that no source can reach.
+pub const UNREACHABLE: i32 = std::i32::MAX;
+
+/// A grid of values over the map, indexed `[x][y]` like `Map`, where lower values are closer to the sources.
+pub type DistanceMap = Vec<Vec<i32>>;
 
 pub fn find_astar_path(map: &Box<dyn MapComponent>, start: Point, goal: Point) -> Option<Vec<Point>> {
     let result: Option<(Vec<Point>, u32)> = astar(&start, |p| p.successors(map, &goal),
                        |p| p.distance(&goal) / 3,
                        |p| *p == goal);
     result.and_then(|f| Some(f.0))
-}
\ No newline at end of file
+}
+
+/// Computes the set of points visible from `origin` within `radius`, by casting
+/// rays to the edge of the surrounding square. Walls that block sight are visible,
+/// but nothing behind them is.
+pub fn field_of_view(map: &Box<dyn MapComponent>, origin: Point, radius: i32) -> HashSet<Point> {
+    let mut visible = HashSet::new();
+    visible.insert(origin);
+
+    let mut edge = vec![];
+    for i in -radius..(radius + 1) {
+        edge.push(origin.offset(i, -radius));
+        edge.push(origin.offset(i, radius));
+        edge.push(origin.offset(-radius, i));
+        edge.push(origin.offset(radius, i));
+    }
+
+    for end in edge {
+        for point in origin.line_to(&end).into_iter().skip(1) {
+            if !map.contains(point.x, point.y) {
+                break
+            }
+
+            visible.insert(point);
+            if map.blocks_sight(point.x, point.y) {
+                break
+            }
+        }
+    }
+
+    visible
+}
+
+/// Builds a Dijkstra map by flooding outward from `seeds`, each of which starts at its own value.
+/// Every walkable tile ends up holding the cheapest value reachable from any seed, using the
+/// same movement costs as A*. Entities are ignored since they move around.
+pub fn dijkstra_map(map: &Box<dyn MapComponent>, seeds: &[(Point, i32)]) -> DistanceMap {
+    let tiles = map.get_map();
+    let mut values = vec![vec![UNREACHABLE; tiles[0].len()]; tiles.len()];
+    let mut frontier = BinaryHeap::new();
+
+    for &(point, value) in seeds {
+        if map.contains(point.x, point.y) && value < values[point.x as usize][point.y as usize] {
+            values[point.x as usize][point.y as usize] = value;
+            frontier.push(Reverse((value, point)));
+        }
+    }
+
+    while let Some(Reverse((value, point))) = frontier.pop() {
+        if value > values[point.x as usize][point.y as usize] {
+            continue
+        }
+
+        for (idx, i) in [(0, 1), (0, -1), (1, 0), (-1, 0), (-1, -1), (1, 1), (1, -1), (-1, 1)].iter().enumerate() {
+            let next = point.offset(i.0, i.1);
+            if !map.contains(next.x, next.y) || map.is_blocked(next.x, next.y) {
+                continue
+            }
+
+            let cost = map.move_cost(next.x, next.y);
+            let cost = if idx > 3 { cost * 14 / 10 } else { cost };
+            let next_value = value.saturating_add(cost as i32);
+
+            if next_value < values[next.x as usize][next.y as usize] {
+                values[next.x as usize][next.y as usize] = next_value;
+                frontier.push(Reverse((next_value, next)));
+            }
+        }
+    }
+
+    values
+}
+
+/// Builds a "safety map" leading away from `threats`. Rather than just inverting the distance
+/// to the threats, the inverted map is flooded again so fleeing monsters head for open space
+/// instead of running into the nearest dead end.
+pub fn safety_map(map: &Box<dyn MapComponent>, threats: &[Point]) -> DistanceMap {
+    let towards = dijkstra_map(map, &threats.iter().map(|p| (*p, 0)).collect::<Vec<_>>());
+
+    let mut seeds = vec![];
+    for (x, column) in towards.iter().enumerate() {
+        for (y, value) in column.iter().enumerate() {
+            if *value != UNREACHABLE {
+                seeds.push((Point { x: x as i32, y: y as i32 }, -(*value * 6 / 5)));
+            }
+        }
+    }
+
+    dijkstra_map(map, &seeds)
+}
+
+/// Finds the free neighbouring point with a lower value than the one at `from`, preferring
+/// low values that are cheap to step onto so lava and known traps are skirted around.
+pub fn descend(map: &Box<dyn MapComponent>, values: &DistanceMap, from: Point) -> Option<Point> {
+    let current = values[from.x as usize][from.y as usize];
+    let mut best = (UNREACHABLE, None);
+
+    for i in [(0, 1), (0, -1), (1, 0), (-1, 0), (-1, -1), (1, 1), (1, -1), (-1, 1)].iter() {
+        let next = from.offset(i.0, i.1);
+        if !map.contains(next.x, next.y) || map.is_blocked(next.x, next.y) || map.is_occupied(next.x, next.y) {
+            continue
+        }
+
+        let value = values[next.x as usize][next.y as usize];
+        let weighted = value.saturating_add(map.move_cost(next.x, next.y) as i32);
+        if value < current && weighted < best.0 {
+            best = (weighted, Some(next));
+        }
+    }
+
+    best.1
+}
+
+/// Finds the free neighbouring point with the highest value that is higher than the value at `from`,
+/// for moving directly away from the sources of a map.
+pub fn ascend(map: &Box<dyn MapComponent>, values: &DistanceMap, from: Point) -> Option<Point> {
+    let current = values[from.x as usize][from.y as usize];
+    let mut best = (current, None);
+
+    for i in [(0, 1), (0, -1), (1, 0), (-1, 0), (-1, -1), (1, 1), (1, -1), (-1, 1)].iter() {
+        let next = from.offset(i.0, i.1);
+        if !map.contains(next.x, next.y) || map.is_blocked(next.x, next.y) || map.is_occupied(next.x, next.y) {
+            continue
+        }
+
+        let value = values[next.x as usize][next.y as usize];
+        if value != UNREACHABLE && value > best.0 {
+            best = (value, Some(next));
+        }
+    }
+
+    best.1
+}
+
+/// Follows a map downhill from `from` for at most `max_steps`, returning the route taken.
+pub fn trace_descent(map: &Box<dyn MapComponent>, values: &DistanceMap, from: Point, max_steps: usize) -> Vec<Point> {
+    let mut route = vec![];
+    let mut current = from;
+
+    while route.len() < max_steps {
+        match descend(map, values, current) {
+            Some(next) => {
+                route.push(next);
+                current = next;
+            }
+            None => break
+        }
+    }
+
+    route
+}
+
+/// A Dijkstra map along with what it was built from, so it is only rebuilt when that changes.
+struct CachedMap {
+    values: DistanceMap,
+    sources: Vec<Point>,
+    revision: u32,
+}
+
+impl CachedMap {
+    fn new() -> CachedMap {
+        CachedMap { values: vec![], sources: vec![], revision: 0 }
+    }
+
+    /// Rebuilds the map with `build` if the sources or the terrain have changed since it was last built.
+    fn refresh<F>(&mut self, map: &Box<dyn MapComponent>, revision: u32, sources: &[Point], build: F) -> &DistanceMap
+        where F: Fn(&Box<dyn MapComponent>, &[Point]) -> DistanceMap {
+        if self.values.is_empty() || self.revision != revision || self.sources.as_slice() != sources {
+            self.values = build(map, sources);
+            self.sources = sources.to_vec();
+            self.revision = revision;
+        }
+
+        &self.values
+    }
+}
+
+/// The Dijkstra maps shared by every monster on a level. Each one is built at most once
+/// per turn, when the points it is built from move or the terrain changes, so any number
+/// of monsters can navigate by them for the price of a lookup.
+pub struct DijkstraMaps {
+    /// Leads toward a set of targets, usually the player
+    approach: CachedMap,
+    /// Leads away from a set of threats, usually the player
+    flee: CachedMap,
+    /// Leads toward the items lying on the floor
+    items: CachedMap,
+}
+
+impl DijkstraMaps {
+    pub fn new() -> DijkstraMaps {
+        DijkstraMaps { approach: CachedMap::new(), flee: CachedMap::new(), items: CachedMap::new() }
+    }
+
+    /// The map leading toward the nearest of `targets`.
+    pub fn approach(&mut self, map: &Box<dyn MapComponent>, revision: u32, targets: &[Point]) -> &DistanceMap {
+        self.approach.refresh(map, revision, targets, |m, s| dijkstra_map(m, &s.iter().map(|p| (*p, 0)).collect::<Vec<_>>()))
+    }
+
+    /// The safety map leading away from `threats`. See [safety_map](fn.safety_map.html)
+    pub fn flee(&mut self, map: &Box<dyn MapComponent>, revision: u32, threats: &[Point]) -> &DistanceMap {
+        self.flee.refresh(map, revision, threats, safety_map)
+    }
+
+    /// The map leading toward the nearest of the items at `item_positions`.
+    pub fn items(&mut self, map: &Box<dyn MapComponent>, revision: u32, item_positions: &[Point]) -> &DistanceMap {
+        self.items.refresh(map, revision, item_positions, |m, s| dijkstra_map(m, &s.iter().map(|p| (*p, 0)).collect::<Vec<_>>()))
+    }
+}
@@ -0,0 +1,253 @@
+use crate::level::Level;
+use crate::actor::{Actor, Entity, Alertness};
+use crate::action::{Action, WalkAction, WaitAction, FireAction, PickupAction};
+use crate::brain::{BrainComponent, AggroBrainComponent};
+
+/// A node in a behaviour tree. Trees are written as s-expressions, e.g.
+/// `(select (seq (adjacent) (attack)) (hunt))`.
+#[derive(Clone, Debug)]
+pub enum Node {
+    /// Runs its children in order until one doesn't fail
+    Select(Vec<Node>),
+    /// Runs its children in order until one fails or acts
+    Sequence(Vec<Node>),
+    /// Succeeds when its child fails and vice versa. Any action is discarded.
+    Not(Box<Node>),
+    Condition(Condition),
+    Task(Task),
+}
+
+/// A check on the state of the monster and its surroundings.
+#[derive(Clone, Copy, Debug)]
+pub enum Condition {
+    /// The player is in the monster's field of view
+    CanSeePlayer,
+    /// The monster is standing right next to the player
+    Adjacent,
+    /// The player is within the given number of steps
+    PlayerWithin(u32),
+    /// The monster's health is below the given percentage of its maximum
+    HealthBelow(i32),
+    /// The monster isn't asleep
+    Awake,
+    /// There is an item under the monster
+    ItemHere,
+}
+
+/// Something the monster can do with its turn. Tasks fail when they make no sense,
+/// such as attacking with nobody around.
+#[derive(Clone, Copy, Debug)]
+pub enum Task {
+    /// Close in on the player, if they can be seen
+    Approach,
+    /// Hit the player, if they are adjacent
+    Attack,
+    /// Run away from wherever the player was last noticed
+    Flee,
+    /// Back away if the player is closer than the given distance
+    KeepDistance(u32),
+    /// Shoot at the player for the given damage, if they can be seen
+    Shoot(i32),
+    /// Pick up whatever is underfoot
+    PickUp,
+    /// Head for the nearest item on the floor
+    SeekItem,
+    /// Take a random step
+    Wander,
+    /// Do nothing
+    Wait,
+    /// Sleep, wander or chase the way an `AggroBrainComponent` does
+    Hunt,
+}
+
+/// What happened when a node was run.
+enum Outcome {
+    Failed,
+    Passed,
+    Acted(Box<dyn Action>),
+}
+
+/// Everything a node needs to know about the monster running it.
+struct Context<'a> {
+    entity: &'a mut Entity,
+    level: &'a mut Level,
+    aggro: &'a mut AggroBrainComponent,
+    sees_player: bool,
+}
+
+/// A brain driven by a behaviour tree. Perception (sight, hearing and
+/// tracking the player) works the same as for an `AggroBrainComponent`.
+pub struct BehaviourBrainComponent {
+    root: Node,
+    aggro: AggroBrainComponent,
+}
+
+impl BehaviourBrainComponent {
+    /// Convenience method for creating `BehaviourBrainComponent`s.
+    pub fn new(root: Node) -> BehaviourBrainComponent {
+        BehaviourBrainComponent { root, aggro: AggroBrainComponent::new() }
+    }
+}
+
+impl BrainComponent for BehaviourBrainComponent {
+    fn get_action(&mut self, entity: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
+        let target = level.current_actor;
+        let sees_player = self.aggro.perceive(entity, level);
+
+        let mut context = Context { entity, level, aggro: &mut self.aggro, sees_player };
+        match self.root.run(&mut context) {
+            Outcome::Acted(action) => Some(action),
+            _ => Some(box WaitAction { target }),
+        }
+    }
+}
+
+impl Node {
+    fn run(&self, c: &mut Context) -> Outcome {
+        match self {
+            Node::Select(children) => {
+                for child in children {
+                    match child.run(c) {
+                        Outcome::Failed => continue,
+                        outcome => return outcome,
+                    }
+                }
+                Outcome::Failed
+            }
+            Node::Sequence(children) => {
+                for child in children {
+                    match child.run(c) {
+                        Outcome::Passed => continue,
+                        outcome => return outcome,
+                    }
+                }
+                Outcome::Passed
+            }
+            Node::Not(child) => match child.run(c) {
+                Outcome::Failed => Outcome::Passed,
+                _ => Outcome::Failed,
+            },
+            Node::Condition(condition) => if condition.check(c) { Outcome::Passed } else { Outcome::Failed },
+            Node::Task(task) => match task.perform(c) {
+                Some(action) => Outcome::Acted(action),
+                None => Outcome::Failed,
+            },
+        }
+    }
+
+    /// Parses a tree from its s-expression form.
+    pub fn parse(source: &str) -> Result<Node, String> {
+        let tokens = source.replace("(", " ( ").replace(")", " ) ")
+            .split_whitespace().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        let mut pos = 0;
+        let node = Self::parse_node(&tokens, &mut pos)?;
+        if pos != tokens.len() {
+            return Err(format!("unexpected '{}' after the end of the tree", tokens[pos]))
+        }
+
+        Ok(node)
+    }
+
+    fn parse_node(tokens: &[String], pos: &mut usize) -> Result<Node, String> {
+        if tokens.get(*pos).map(|t| t.as_str()) != Some("(") {
+            return Err(format!("expected '(' but found {}", tokens.get(*pos).map_or("the end", |t| t.as_str())))
+        }
+
+        let name = tokens.get(*pos + 1).ok_or("unexpected end of tree")?.clone();
+        *pos += 2;
+
+        let mut children = vec![];
+        let mut args = vec![];
+        loop {
+            match tokens.get(*pos).map(|t| t.as_str()) {
+                None => return Err(format!("unclosed '({}'", name)),
+                Some(")") => {
+                    *pos += 1;
+                    break
+                }
+                Some("(") => children.push(Self::parse_node(tokens, pos)?),
+                Some(arg) => {
+                    args.push(arg.parse::<i32>().map_err(|_| format!("'{}' is not a number", arg))?);
+                    *pos += 1;
+                }
+            }
+        }
+
+        let is_leaf = children.is_empty();
+        let arg = |i: usize| args.get(i).cloned().ok_or(format!("'{}' needs an argument", name));
+        let leaf = |node: Node| if is_leaf { Ok(node) } else { Err(format!("'{}' can't have children", name)) };
+
+        match name.as_str() {
+            "select" => Ok(Node::Select(children)),
+            "seq" => Ok(Node::Sequence(children)),
+            "not" if children.len() == 1 => Ok(Node::Not(box children.remove(0))),
+            "not" => Err("'not' takes exactly one child".to_string()),
+            "can-see-player" => leaf(Node::Condition(Condition::CanSeePlayer)),
+            "adjacent" => leaf(Node::Condition(Condition::Adjacent)),
+            "player-within" => leaf(Node::Condition(Condition::PlayerWithin(arg(0)? as u32))),
+            "health-below" => leaf(Node::Condition(Condition::HealthBelow(arg(0)?))),
+            "awake" => leaf(Node::Condition(Condition::Awake)),
+            "item-here" => leaf(Node::Condition(Condition::ItemHere)),
+            "approach" => leaf(Node::Task(Task::Approach)),
+            "attack" => leaf(Node::Task(Task::Attack)),
+            "flee" => leaf(Node::Task(Task::Flee)),
+            "keep-distance" => leaf(Node::Task(Task::KeepDistance(arg(0)? as u32))),
+            "shoot" => leaf(Node::Task(Task::Shoot(arg(0)?))),
+            "pick-up" => leaf(Node::Task(Task::PickUp)),
+            "seek-item" => leaf(Node::Task(Task::SeekItem)),
+            "wander" => leaf(Node::Task(Task::Wander)),
+            "wait" => leaf(Node::Task(Task::Wait)),
+            "hunt" => leaf(Node::Task(Task::Hunt)),
+            _ => Err(format!("unknown node '{}'", name)),
+        }
+    }
+}
+
+impl Condition {
+    fn check(&self, c: &mut Context) -> bool {
+        let position = c.entity.get_position();
+        let player_pos = c.level.entities[0].as_ref().unwrap().get_position();
+
+        match *self {
+            Condition::CanSeePlayer => c.sees_player,
+            Condition::Adjacent => position.is_adjacent(&player_pos),
+            Condition::PlayerWithin(distance) => c.sees_player && position.distance(&player_pos) <= distance,
+            Condition::HealthBelow(percent) => c.entity.health * 100 < c.entity.max_health * percent,
+            Condition::Awake => c.entity.alertness != Alertness::Asleep,
+            Condition::ItemHere => c.level.items.contains_key(&position),
+        }
+    }
+}
+
+impl Task {
+    fn perform(&self, c: &mut Context) -> Option<Box<dyn Action>> {
+        let target = c.level.current_actor;
+        let position = c.entity.get_position();
+        let player_pos = c.level.entities[0].as_ref().unwrap().get_position();
+
+        match *self {
+            Task::Approach if c.sees_player => c.aggro.close_in(position, player_pos, c.level),
+            Task::Attack if position.is_adjacent(&player_pos) => Some(box WalkAction::from_point(player_pos, target)),
+            Task::Flee => {
+                let threat = c.aggro.last_known()?;
+                let step = c.level.flee_step(position, &[threat])?;
+                Some(box WalkAction::from_point(step, target))
+            }
+            Task::KeepDistance(distance) if c.sees_player && position.distance(&player_pos) < distance => {
+                let step = c.level.flee_step(position, &[player_pos])?;
+                Some(box WalkAction::from_point(step, target))
+            }
+            Task::Shoot(damage) if c.sees_player => Some(box FireAction { target, at: player_pos, damage }),
+            Task::PickUp if c.level.items.contains_key(&position) => Some(box PickupAction::new(target)),
+            Task::SeekItem => {
+                let step = c.level.item_step(position)?;
+                Some(box WalkAction::from_point(step, target))
+            }
+            Task::Wander => c.aggro.wander(c.entity, c.level),
+            Task::Wait => Some(box WaitAction { target }),
+            Task::Hunt => c.aggro.pursue(c.entity, c.level),
+            _ => None,
+        }
+    }
+}
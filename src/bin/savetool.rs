@@ -0,0 +1,210 @@
+#![feature(box_syntax)]
+//! Looks inside save files without starting the game, for debugging saves players send in
+
+use roguelike::save::{self, SaveData, SAVE_VERSION};
+use roguelike::replay::{self, Replay};
+use roguelike::game::{Game, GameSettings};
+use roguelike::state::{self, PlayState, State};
+use roguelike::keymap::Keymap;
+use roguelike::{monster, morgue};
+
+use std::fs;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "\
+Usage: savetool <command> <save>
+
+Commands:
+    dump <save>              Print the save as JSON
+    check <save>             Check the save loads, and that its replay plays back to where it was saved
+    migrate <save> [<out>]   Rewrite the save in the current format, in place unless <out> is given.
+                             The original is kept as <save>.bak
+    seed <save>              Print the save's seed
+    map <save>               Print the whole level the save was on, with what's on it
+    help                     Show this message";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+
+    let result = match args.as_slice() {
+        ["dump", path] => dump(Path::new(path)),
+        ["check", path] => check(Path::new(path)),
+        ["migrate", path] => migrate(Path::new(path), Path::new(path)),
+        ["migrate", path, out] => migrate(Path::new(path), Path::new(out)),
+        ["seed", path] => read(Path::new(path)).map(|data| println!("{}", data.seed)),
+        ["map", path] => map(Path::new(path)),
+        ["help"] | ["--help"] => {
+            println!("{}", USAGE);
+            return
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+/// Reads a save, carrying on with a warning if it's corrupted but can still be made sense of
+fn read(path: &Path) -> Result<SaveData, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e.to_string()))?;
+    if let Err(e) = save::verify(&contents) {
+        eprintln!("warning: {}; reading it anyway", e);
+    }
+
+    save::decode_unverified(&contents).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+}
+
+fn dump(path: &Path) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e.to_string()))?;
+    let data = read(path)?;
+
+    let mut lines = vec![
+        "{".to_string(),
+        format!("  \"format\": {},", save::version(&contents)?),
+        format!("  \"checksum_ok\": {},", save::verify(&contents).is_ok()),
+        format!("  \"seed\": {},", data.seed),
+        format!("  \"content\": {},", data.content.map_or("null".to_string(), |c| c.to_string())),
+        format!("  \"wizard\": {},", data.wizard),
+        format!("  \"depth\": {},", data.depth),
+        format!("  \"turns\": {},", data.turns),
+        // Saves don't keep any of the levels; they're made again by playing the replay back
+        format!("  \"rebuilt_from_replay\": {},", json_list(&["levels".to_string(), "container contents".to_string(), "locked containers".to_string()])),
+    ];
+
+    match &data.replay {
+        Some(replay) => {
+            let settings = replay.settings;
+            lines.push("  \"replay\": {".to_string());
+            lines.push(format!("    \"seed\": {},", replay.seed));
+            lines.push(format!("    \"settings\": {{ \"width\": {}, \"height\": {}, \"generator\": {}, \"wizard\": {} }},",
+                settings.width, settings.height, json_string(settings.generator.name()), settings.wizard));
+            lines.push(format!("    \"monsters\": {},", replay.monsters.map_or("null".to_string(), |h| json_string(&format!("{:016x}", h)))));
+            lines.push(format!("    \"keymap\": {},", json_list(&replay.keymap.to_lines())));
+            lines.push(format!("    \"keys\": {}", json_list(&replay.keys.iter().map(replay::format_key).collect::<Vec<_>>())));
+            lines.push("  }".to_string());
+        }
+        None => lines.push("  \"replay\": null".to_string()),
+    }
+    lines.push("}".to_string());
+
+    println!("{}", lines.join("\n"));
+    Ok(())
+}
+
+/// Checks the save strictly, as the game would, then plays it back. Any problem is an error.
+fn check(path: &Path) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e.to_string()))?;
+    let version = save::version(&contents)?;
+    let data = save::decode(&contents)?;
+
+    let mut problems = vec![];
+    if version < SAVE_VERSION {
+        problems.push(format!("the save is in format {}, which starts the game over (migrate it to format {})", version, SAVE_VERSION));
+    }
+    if let Err(e) = data.check_content() {
+        problems.push(e);
+    }
+
+    if let Some(replay) = &data.replay {
+        if replay.seed != data.seed {
+            problems.push(format!("the save's seed is {}, but its replay's is {}", data.seed, replay.seed));
+        }
+
+        let game = play_back(&data)?;
+        if (game.turns, game.depth) != (data.turns, data.depth) {
+            problems.push(format!("the replay ends on turn {} at depth {}, but the game was saved on turn {} at depth {}",
+                game.turns, game.depth, data.turns, data.depth));
+        }
+        if game.level.entities[0].as_ref().unwrap().is_dead() {
+            problems.push("the replay ends with the player dead".to_string());
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems.join("\n"))
+    }
+
+    println!("{} is a good format {} save", path.display(), version);
+    Ok(())
+}
+
+/// Rewrites an older save in the current format. Older saves only kept the seed,
+/// so they stay starting over from it. The content version is kept as it was, since
+/// rewriting a save doesn't change which levels it plays back into.
+fn migrate(path: &Path, out: &Path) -> Result<(), String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e.to_string()))?;
+    let version = save::version(&contents)?;
+    let data = save::decode(&contents)?;
+
+    if version == SAVE_VERSION {
+        println!("{} is already in format {}", path.display(), SAVE_VERSION);
+        return Ok(())
+    }
+
+    let mut backup = path.as_os_str().to_os_string();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    fs::copy(path, &backup).map_err(|e| format!("Could not back up {}: {}", path.display(), e.to_string()))?;
+
+    save::write_atomically(out, &save::encode(&data)).map_err(|e| format!("Could not write {}: {}", out.display(), e.to_string()))?;
+    println!("Migrated {} from format {} to {} (the original is at {})", out.display(), version, SAVE_VERSION, backup.display());
+    Ok(())
+}
+
+/// Prints the level the game was saved on. What the player had explored isn't known
+/// without a window to work out what they could see, so all of it is shown.
+fn map(path: &Path) -> Result<(), String> {
+    let data = read(path)?;
+    let mut game = play_back(&data)?;
+    game.show_map = true;
+
+    println!("Seed {}, depth {}, turn {}:", game.seed, game.depth, game.turns);
+    for line in morgue::map_snapshot(&game) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Plays the save's game back without a window to where it was saved, with the monster
+/// catalogue in the directory savetool is run from. Saves from before replays were kept
+/// start over from their seed, so that's where they're left.
+fn play_back(data: &SaveData) -> Result<Game, String> {
+    let monsters = monster::catalogue();
+    let replay = data.replay.clone().unwrap_or_else(|| {
+        let settings = GameSettings { wizard: data.wizard, ..GameSettings::default() };
+        Replay::new(data.seed, settings, &monsters, Keymap::default())
+    });
+
+    let game = Game::from_replay(replay, monsters, Duration::from_millis(0), true)?;
+    let mut states: Vec<Box<dyn State>> = vec![box PlayState::new(game)];
+    state::run(&mut states, false);
+    Ok(states.pop().unwrap().exit())
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_list(items: &[String]) -> String {
+    format!("[{}]", items.iter().map(|i| json_string(i)).collect::<Vec<_>>().join(", "))
+}
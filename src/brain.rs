@@ -1,17 +1,32 @@
 use crate::level::Level;
 
-use crate::ai::{SHOW_AI, find_astar_path};
+use crate::ai::{find_astar_path, field_of_view, trace_descent};
 
 use crate::util::{Point, Bound};
 
-use crate::actor::{Actor, Entity};
+use crate::actor::{Actor, Entity, Alertness};
 
-use crate::map::MapComponent;
+use crate::map::Rect;
+
+use crate::keymap::Command;
 
 use tcod::colors::Color;
 use rand::Rng;
 
-use crate::action::{WalkAction, WaitAction, Action, PickupAction};
+use crate::action::{WalkAction, WaitAction, Action, PickupAction, SearchAction, FireAction, UseAction, OpenAction, KickAction};
+
+/// Terrain at least this expensive to cross is never wandered into.
+const AVOID_COST: u32 = 100;
+/// How far monsters can see.
+const MONSTER_FOV: i32 = 8;
+/// How many turns a monster searches for the player after losing track of them.
+const SEARCH_TIMEOUT: u32 = 10;
+/// How much of a monster's route to draw when showing the AI.
+const MAX_TRACE: usize = 50;
+/// How many random spots a wanderer tries before giving up on moving this turn.
+const MAX_DESTINATION_TRIES: u32 = 10;
+/// The longest a wanderer idles once it arrives somewhere.
+const MAX_IDLE_TURNS: u32 = 5;
 
 /// A trait for defining a method of movement
 /// that may be applied to any living monster.
@@ -21,13 +36,55 @@ pub trait BrainComponent {
 }
 
 /// A movement component that uses A* to find the
-/// fastest path to the player.
+/// fastest path to the player once it has been woken up.
 pub struct AggroBrainComponent {
     path: Vec<Point>,
+    /// Where the player was last seen or heard
+    last_known: Option<Point>,
+    /// How many turns have been spent looking for the player since losing track of them
+    search_turns: u32,
+    /// Moves the monster around while it isn't after anyone
+    wanderer: RandomBrainComponent,
 }
 
+/// A brain that hunts like an `AggroBrainComponent`, but flees
+/// along a safety map once it has been badly hurt.
+pub struct CowardBrainComponent {
+    aggro: AggroBrainComponent,
+}
+
+/// A brain that keeps its distance from the player and shoots
+/// at them whenever they are in sight.
+pub struct ArcherBrainComponent {
+    aggro: AggroBrainComponent,
+    /// How close the archer lets the player get before backing off
+    preferred_distance: u32,
+    /// How much damage each shot deals
+    damage: i32,
+}
+
+/// A brain for monsters that hunt in groups, each taking a
+/// different side of the player to surround them.
+pub struct PackBrainComponent {
+    aggro: AggroBrainComponent,
+}
+
+/// A brain that strolls between random reachable spots,
+/// idling for a while whenever it gets to one.
 pub struct RandomBrainComponent {
-    bounds: Bound,
+    /// Where the wanderer may go, or `None` for anywhere on the map
+    bounds: Option<Bound>,
+    path: Vec<Point>,
+    /// How many more turns to stand around before moving on
+    idle_turns: u32,
+}
+
+/// A brain for shopkeepers, who potter about their shop until they're
+/// robbed, then hunt the thief down like an `AggroBrainComponent`.
+pub struct ShopkeeperBrainComponent {
+    /// Keeps the shopkeeper in their shop
+    minder: RandomBrainComponent,
+    aggro: AggroBrainComponent,
 }
 
 pub struct NoBrainComponent;
@@ -37,45 +94,265 @@ pub struct PlayerBrainComponent;
 impl AggroBrainComponent {
     /// Convenience method for creating `AggroMovementComponent`s.
     pub fn new() -> AggroBrainComponent {
-        AggroBrainComponent { path: vec![] }
+        AggroBrainComponent { path: vec![], last_known: None, search_turns: 0, wanderer: RandomBrainComponent::anywhere() }
     }
 
-    fn show_ai(&mut self, map: &mut Box<dyn MapComponent>) {
-        if SHOW_AI {
+    fn show_ai(&mut self, level: &mut Level) {
+        if level.show_ai {
             if !self.path.is_empty() {
                 for point in &self.path {
-                    map.get_map_mut()[point.x as usize][point.y as usize].color_override = Some(Color { r: 255, g: 0, b: 0 });
+                    level.map_component.get_map_mut()[point.x as usize][point.y as usize].color_override = Some(Color { r: 255, g: 0, b: 0 });
                 }
             }
         }
     }
+
+    /// Where the player was last seen or heard, if anywhere.
+    pub fn last_known(&self) -> Option<Point> {
+        self.last_known
+    }
+
+    /// Takes one step along the A* path from `start` to `goal`, if there is one.
+    pub fn step_towards(&mut self, start: Point, goal: Point, level: &mut Level) -> Option<Box<dyn Action>> {
+        let target = level.current_actor;
+        let mut path = find_astar_path(&level.map_component, start, goal)?;
+
+        // 0th element is the current position
+        path.remove(0);
+        self.path = path;
+
+        if self.path.is_empty() {
+            return None
+        }
+
+        // Investigating a noise is no reason to stand in lava or on a known trap
+        if self.path[0] == goal && !level.map_component.is_occupied(goal.x, goal.y)
+            && level.map_component.move_cost(goal.x, goal.y) >= AVOID_COST {
+            return None
+        }
+
+        self.show_ai(level);
+        Some(box WalkAction::from_point(self.path.remove(0), target))
+    }
+
+    /// Closes in on the player along the approach map shared by every monster on the level.
+    pub fn close_in(&mut self, start: Point, player_pos: Point, level: &mut Level) -> Option<Box<dyn Action>> {
+        let target = level.current_actor;
+
+        if start.is_adjacent(&player_pos) {
+            return Some(box WalkAction::from_point(player_pos, target))
+        }
+
+        let step = level.approach_step(start, &[player_pos])?;
+
+        if level.show_ai {
+            let values = level.dijkstra.approach(&level.map_component, level.map_revision, &[player_pos]);
+            self.path = trace_descent(&level.map_component, values, start, MAX_TRACE);
+            self.show_ai(level);
+        }
+
+        Some(box WalkAction::from_point(step, target))
+    }
+
+    /// Takes a random step, steering clear of lava and known traps.
+    pub fn wander(&self, entity: &Entity, level: &mut Level) -> Option<Box<dyn Action>> {
+        let target = level.current_actor;
+        let offset = Point { x: level.rng.ai.gen_range(-1, 2), y: level.rng.ai.gen_range(-1, 2) };
+        let step = entity.get_position().offset(offset.x, offset.y);
+
+        if level.map_component.move_cost(step.x, step.y) < AVOID_COST {
+            Some(box WalkAction::from_offset(offset, target))
+        } else {
+            Some(box WaitAction { target })
+        }
+    }
 }
 
 impl BrainComponent for AggroBrainComponent {
     fn get_action(&mut self, entity: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
+        self.perceive(entity, level);
+        self.pursue(entity, level)
+    }
+}
+
+impl AggroBrainComponent {
+    /// Updates the monster's idea of where the player is from what it can see and hear,
+    /// returning whether the player is in plain sight.
+    pub fn perceive(&mut self, entity: &mut Entity, level: &mut Level) -> bool {
+        let position = entity.get_position();
         let player_pos = level.entities[0].as_ref().unwrap().get_position();
+
+        // Sleeping monsters can only be woken by noise
+        let sees_player = entity.alertness != Alertness::Asleep
+            && field_of_view(&level.map_component, position, MONSTER_FOV).contains(&player_pos);
+
+        if sees_player {
+            entity.alertness = Alertness::Hunting;
+            self.last_known = Some(player_pos);
+            self.search_turns = 0;
+        } else if let Some(origin) = level.heard_noise(position, entity.alertness) {
+            entity.alertness = Alertness::Hunting;
+            self.last_known = Some(origin);
+            self.search_turns = 0;
+        }
+
+        sees_player
+    }
+
+    /// Does whatever the monster's alertness calls for: sleeping, wandering, or chasing
+    /// down the last place the player was noticed.
+    pub fn pursue(&mut self, entity: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
+        let target = level.current_actor;
+        let position = entity.get_position();
+
+        match entity.alertness {
+            Alertness::Asleep => Some(box WaitAction { target }),
+            Alertness::Wandering => self.wanderer.get_action(entity, level),
+            Alertness::Hunting => {
+                if let Some(goal) = self.last_known {
+                    let player_pos = level.entities[0].as_ref().unwrap().get_position();
+
+                    // Every monster chasing the player shares one map, anywhere else needs its own path
+                    let action = if goal == player_pos {
+                        self.close_in(position, player_pos, level)
+                    } else {
+                        self.step_towards(position, goal, level)
+                    };
+
+                    if action.is_some() {
+                        return action
+                    }
+                }
+
+                // Nothing left to chase, so look around for a while before giving up
+                self.search_turns += 1;
+                if self.search_turns > SEARCH_TIMEOUT {
+                    entity.alertness = Alertness::Wandering;
+                    self.last_known = None;
+                    self.search_turns = 0;
+                }
+
+                self.wander(entity, level)
+            }
+        }
+    }
+}
+
+impl CowardBrainComponent {
+    /// Convenience method for creating `CowardBrainComponent`s.
+    pub fn new() -> CowardBrainComponent {
+        CowardBrainComponent { aggro: AggroBrainComponent::new() }
+    }
+}
+
+impl BrainComponent for CowardBrainComponent {
+    fn get_action(&mut self, entity: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
+        self.aggro.perceive(entity, level);
+
+        if let (true, Alertness::Hunting, Some(threat)) = (entity.is_hurt(), entity.alertness, self.aggro.last_known) {
+            // With nowhere left to run, it fights like anything else
+            if let Some(step) = level.flee_step(entity.get_position(), &[threat]) {
+                return Some(box WalkAction::from_point(step, level.current_actor))
+            }
+        }
+
+        self.aggro.pursue(entity, level)
+    }
+}
+
+impl ArcherBrainComponent {
+    /// Convenience method for creating `ArcherBrainComponent`s.
+    pub fn new(preferred_distance: u32, damage: i32) -> ArcherBrainComponent {
+        ArcherBrainComponent { aggro: AggroBrainComponent::new(), preferred_distance, damage }
+    }
+}
+
+impl BrainComponent for ArcherBrainComponent {
+    fn get_action(&mut self, entity: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
         let target = level.current_actor;
 
-        let path_opt = find_astar_path(&level.map_component, entity.get_position(), player_pos);
+        if self.aggro.perceive(entity, level) {
+            let position = entity.get_position();
+            let player_pos = level.entities[0].as_ref().unwrap().get_position();
 
-        if let Some(path) = path_opt {
-            self.path = path;
+            if position.distance(&player_pos) < self.preferred_distance {
+                if let Some(step) = level.flee_step(position, &[player_pos]) {
+                    return Some(box WalkAction::from_point(step, target))
+                }
+            }
+
+            return Some(box FireAction { target, at: player_pos, damage: self.damage })
+        }
 
-            // 0th element is the current position
-            self.path.remove(0);
+        self.aggro.pursue(entity, level)
+    }
+}
 
-            return if !self.path.is_empty() {
-                self.show_ai(&mut level.map_component);
-                Some(box WalkAction::from_point(self.path.remove(0), target))
-            } else {
-                Some(box WaitAction { target })
+impl PackBrainComponent {
+    /// Convenience method for creating `PackBrainComponent`s.
+    pub fn new() -> PackBrainComponent {
+        PackBrainComponent { aggro: AggroBrainComponent::new() }
+    }
+}
+
+impl BrainComponent for PackBrainComponent {
+    fn get_action(&mut self, entity: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
+        let target = level.current_actor;
+
+        if !self.aggro.perceive(entity, level) {
+            return self.aggro.pursue(entity, level)
+        }
+
+        let position = entity.get_position();
+        let player_pos = level.entities[0].as_ref().unwrap().get_position();
+
+        if position.is_adjacent(&player_pos) {
+            level.pack_claims.push(position);
+            return Some(box WalkAction::from_point(player_pos, target))
+        }
+
+        // Head for the closest side of the player that no other pack member is going for
+        let mut slots = vec![];
+        for x in (player_pos.x - 1)..(player_pos.x + 2) {
+            for y in (player_pos.y - 1)..(player_pos.y + 2) {
+                let slot = Point { x, y };
+                if slot != player_pos && level.map_component.contains(x, y) && !level.map_component.is_blocked(x, y)
+                    && !level.map_component.is_occupied(x, y) && !level.pack_claims.contains(&slot) {
+                    slots.push(slot);
+                }
+            }
+        }
+        slots.sort_by_key(|slot| slot.distance(&position));
+
+        for slot in slots {
+            if let Some(action) = self.aggro.step_towards(position, slot, level) {
+                level.pack_claims.push(slot);
+                return Some(action)
             }
         }
 
+        // Every side is taken, so hang back rather than queue up behind the others
         Some(box WaitAction { target })
     }
 }
 
+impl ShopkeeperBrainComponent {
+    /// Convenience method for creating a `ShopkeeperBrainComponent` minding the shop in `room`.
+    pub fn new(room: &Rect) -> ShopkeeperBrainComponent {
+        ShopkeeperBrainComponent { minder: RandomBrainComponent::in_room(room), aggro: AggroBrainComponent::new() }
+    }
+}
+
+impl BrainComponent for ShopkeeperBrainComponent {
+    fn get_action(&mut self, entity: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
+        if entity.is_hostile {
+            self.aggro.get_action(entity, level)
+        } else {
+            self.minder.get_action(entity, level)
+        }
+    }
+}
+
 impl PlayerBrainComponent {
     /// Convenience method for creating `PlayerBrainComponents`.
     pub fn new() -> PlayerBrainComponent {
@@ -85,57 +362,111 @@ impl PlayerBrainComponent {
 
 impl BrainComponent for PlayerBrainComponent {
     fn get_action(&mut self, _e: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
-        use crate::action::Direction::*;
-        use tcod::input::KeyCode::*;
-
         let target = level.current_actor;
 
-        // TODO: Consider replacing this match with a method named something like "match_action"
-        match level.input {
-            Some(keypress) => {
-                match (keypress.code, keypress.printable) {
-                    (NumPad8, _) | (Up, _) => Some(box WalkAction::new(N, target)),
-                    (NumPad2, _) | (Down, _) => Some(box WalkAction::new(S, target)),
-                    (NumPad4, _) | (Left, _) => Some(box WalkAction::new(W, target)),
-                    (NumPad6, _) | (Right, _) => Some(box WalkAction::new(E,target)),
-                    (NumPad7, _) => Some(box WalkAction::new(NW, target)),
-                    (NumPad9, _) => Some(box WalkAction::new(NE, target)),
-                    (NumPad1, _) => Some(box WalkAction::new(SW, target)),
-                    (NumPad3, _) => Some(box WalkAction::new(SE, target)),
-                    (Char, '.') => Some(box WaitAction { target }),
-                    (Char, 'g') => Some(box PickupAction { target }),
-                    _ => None
-                }
+        match level.command? {
+            Command::Move(direction) => Some(box WalkAction::new(direction, target)),
+            Command::Wait => Some(box WaitAction { target }),
+            Command::PickUp => Some(box PickupAction { target, choice: level.pickup_choice.take() }),
+            Command::Use => {
+                let (item, at) = level.use_choice.take()?;
+                Some(box UseAction { target, item, at })
             }
-            None => None
+            Command::Open => {
+                let (container, take, put) = level.open_choice.take()?;
+                Some(box OpenAction { target, container, take, put })
+            }
+            Command::Kick => {
+                let direction = level.kick_choice.take()?;
+                Some(box KickAction { target, direction })
+            }
+            Command::Search => Some(box SearchAction { target }),
+            // Leaving the game, menus and the wizard's tools are up to the PlayState
+            Command::Quit | Command::SaveAndQuit | Command::Inventory | Command::Wizard | Command::Console => None,
         }
     }
 }
 
 impl RandomBrainComponent {
-    /// Convenience method for creating `RandomMovementComponents`.
+    /// Convenience method for creating `RandomMovementComponents` that stay within `bound`.
     pub fn new(bound: Bound) -> RandomBrainComponent {
-        RandomBrainComponent { bounds: bound }
+        RandomBrainComponent { bounds: Some(bound), path: vec![], idle_turns: 0 }
+    }
+
+    /// Creates a `RandomBrainComponent` that stays on the floor of `room`.
+    pub fn in_room(room: &Rect) -> RandomBrainComponent {
+        Self::new(Bound {
+            min: Point { x: room.x1 + 1, y: room.y1 + 1 },
+            max: Point { x: room.x2 - 1, y: room.y2 - 1 },
+        })
+    }
+
+    /// Creates a `RandomBrainComponent` free to roam the whole map.
+    pub fn anywhere() -> RandomBrainComponent {
+        RandomBrainComponent { bounds: None, path: vec![], idle_turns: 0 }
+    }
+
+    /// Picks a random spot within the bounds that can be reached from `start`, and plans a path there.
+    fn plan_route(&mut self, start: Point, level: &mut Level) {
+        let bounds = self.bounds.unwrap_or(level.map_component.get_bounds());
+
+        for _ in 0..MAX_DESTINATION_TRIES {
+            let destination = Point {
+                x: level.rng.ai.gen_range(bounds.min.x, bounds.max.x + 1),
+                y: level.rng.ai.gen_range(bounds.min.y, bounds.max.y + 1),
+            };
+
+            let map = &level.map_component;
+            if destination == start || !map.contains(destination.x, destination.y) || map.is_blocked(destination.x, destination.y)
+                || map.move_cost(destination.x, destination.y) >= AVOID_COST {
+                continue
+            }
+
+            if let Some(mut path) = find_astar_path(map, start, destination) {
+                // 0th element is the current position
+                path.remove(0);
+                self.path = path;
+                return
+            }
+        }
     }
 }
 
 impl BrainComponent for RandomBrainComponent {
-    fn get_action(&mut self, _e: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
-        let offset = Point { x: 0, y: 0 };
+    fn get_action(&mut self, entity: &mut Entity, level: &mut Level) -> Option<Box<dyn Action>> {
+        let target = level.current_actor;
+
+        if self.idle_turns > 0 {
+            self.idle_turns -= 1;
+            return Some(box WaitAction { target })
+        }
+
+        // A stale route from before the monster was off doing something else is no use
+        if !self.path.is_empty() && !self.path[0].is_adjacent(&entity.get_position()) {
+            self.path.clear();
+        }
+
+        if self.path.is_empty() {
+            self.plan_route(entity.get_position(), level);
+        }
 
-        let mut offset_x = rand::thread_rng().gen_range(-1, 2i32);
-        while !self.bounds.contains(offset.offset_x(offset_x)) {
-            offset_x = rand::thread_rng().gen_range(-1, 2i32);
+        if self.path.is_empty() {
+            return Some(box WaitAction { target })
         }
 
-        let mut offset_y = rand::thread_rng().gen_range(-1, 2i32);
-        while !self.bounds.contains(offset.offset_y(offset_y)) {
-            offset_y = rand::thread_rng().gen_range(-1, 2i32);
+        let next = self.path.remove(0);
+        if level.map_component.is_occupied(next.x, next.y) {
+            // Someone is in the way, so find somewhere else to go
+            self.path.clear();
+            return Some(box WaitAction { target })
         }
 
-        offset.offset(offset_x, offset_y);
+        // Take a breather on arrival
+        if self.path.is_empty() {
+            self.idle_turns = level.rng.ai.gen_range(0, MAX_IDLE_TURNS + 1);
+        }
 
-        Some(box WalkAction::from_offset(offset, level.current_actor))
+        Some(box WalkAction::from_point(next, target))
     }
 }
 
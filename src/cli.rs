@@ -0,0 +1,110 @@
+use crate::game::GameSettings;
+use crate::map::Generator;
+
+use std::path::PathBuf;
+
+/// How long a watched replay lingers on each key by default, in milliseconds
+const DEFAULT_REPLAY_DELAY: u64 = 100;
+/// The smallest map the interface can fit around
+const MIN_MAP_SIZE: (i32, i32) = (40, 20);
+/// The largest map that's sensible to open a window for
+const MAX_MAP_SIZE: (i32, i32) = (200, 100);
+
+pub const USAGE: &str = "\
+Usage: roguelike [OPTIONS]
+
+Options:
+    --seed <n>            Skip the main menu and start a new game from the given seed
+    --new-game            Skip the main menu and start a new game
+    --save-file <path>    Save to and load from <path> instead of a slot in ~/.config/mrtom/saves
+    --wizard              Start in wizard mode, showing the whole map and what monsters
+                          are planning. F12 opens the wizard menu
+    --width <n>           The width of the map (default 80)
+    --height <n>          The height of the map (default 50)
+    --generator <name>    How levels are laid out: dungeon or arena (default arena)
+    --replay <path>       Play back a recorded game
+    --delay <ms>          How long a replay waits between keys (default 100)
+    --headless            Play the replay without a window and print where it ended
+    --help                Show this message";
+
+/// Everything that can be chosen on the command line
+pub struct Options {
+    /// The seed to start a new game with
+    pub seed: Option<u64>,
+    /// Where to save to and load from, if not the default
+    pub save_file: Option<PathBuf>,
+    /// Whether to ignore any existing save
+    pub new_game: bool,
+    /// The settings for a new game
+    pub settings: GameSettings,
+    /// A replay file to play back
+    pub replay: Option<PathBuf>,
+    /// How long a replay waits between keys, in milliseconds
+    pub delay: u64,
+    /// Whether to play the replay without a window
+    pub headless: bool,
+    /// Whether to just print the usage
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            seed: None,
+            save_file: None,
+            new_game: false,
+            settings: GameSettings::default(),
+            replay: None,
+            delay: DEFAULT_REPLAY_DELAY,
+            headless: false,
+            help: false,
+        }
+    }
+}
+
+/// Parses the command line arguments, not including the program name
+pub fn parse<I: Iterator<Item=String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => options.seed = Some(expect_number(&arg, args.next())?),
+            "--new-game" => options.new_game = true,
+            "--save-file" => options.save_file = Some(PathBuf::from(expect_value(&arg, args.next())?)),
+            "--wizard" => options.settings.wizard = true,
+            "--width" => options.settings.width = expect_number(&arg, args.next())?,
+            "--height" => options.settings.height = expect_number(&arg, args.next())?,
+            "--generator" => {
+                let name = expect_value(&arg, args.next())?;
+                options.settings.generator = Generator::from_name(&name)
+                    .ok_or(format!("unknown generator '{}'", name))?;
+            }
+            "--replay" => options.replay = Some(PathBuf::from(expect_value(&arg, args.next())?)),
+            "--delay" => options.delay = expect_number(&arg, args.next())?,
+            "--headless" => options.headless = true,
+            "--help" | "-h" => options.help = true,
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+
+    let (width, height) = (options.settings.width, options.settings.height);
+    if width < MIN_MAP_SIZE.0 || height < MIN_MAP_SIZE.1 || width > MAX_MAP_SIZE.0 || height > MAX_MAP_SIZE.1 {
+        return Err(format!("the map must be between {}x{} and {}x{}",
+                           MIN_MAP_SIZE.0, MIN_MAP_SIZE.1, MAX_MAP_SIZE.0, MAX_MAP_SIZE.1))
+    }
+
+    if options.headless && options.replay.is_none() {
+        return Err("--headless only works with --replay".to_string())
+    }
+
+    Ok(options)
+}
+
+fn expect_value(flag: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or(format!("{} needs a value", flag))
+}
+
+fn expect_number<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = expect_value(flag, value)?;
+    value.parse::<T>().map_err(|_| format!("{} needs a number, not '{}'", flag, value))
+}
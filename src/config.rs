@@ -0,0 +1,41 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use dirs::home_dir;
+
+/// The game's config directory: `$XDG_CONFIG_HOME/mrtom`, falling back on
+/// `~/.config/mrtom`. `None` if neither can be found.
+pub fn config_dir() -> Option<PathBuf> {
+    // The spec says relative paths are to be ignored
+    let base = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).filter(|p| p.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".config")))?;
+
+    Some(base.join("mrtom"))
+}
+
+/// Where a file in the game's config directory lives, if there's a config directory to put it in
+pub fn config_path(file: &str) -> Option<PathBuf> {
+    Some(config_dir()?.join(file))
+}
+
+/// The current time in seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Formats a time in seconds since the Unix epoch as a UTC date, e.g. "2019-06-01"
+pub fn format_date(time: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm
+    let days = (time / 86400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
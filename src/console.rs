@@ -0,0 +1,232 @@
+use crate::game::Game;
+use crate::state::State;
+use crate::util::Point;
+use crate::actor::Actor;
+use crate::wizard;
+use crate::Exit;
+
+use tcod::input::KeyCode;
+
+/// How many lines of the message log the console shows above its prompt
+const CONSOLE_LINES: usize = 10;
+
+/// Every command the console knows, with its usage
+const COMMANDS: [(&str, &str); 9] = [
+    ("help", "help: list the commands"),
+    ("spawn", "spawn <monster> [x y]: spawn a monster, next to you or at (x, y)"),
+    ("give", "give <item> [n]: put an item, or n of them, in your pack"),
+    ("tp", "tp <x> <y>: teleport to (x, y)"),
+    ("heal", "heal: restore your health"),
+    ("depth", "depth <n>: go to depth n"),
+    ("dump", "dump entities|items: list what's on the level"),
+    ("regen", "regen level: replace the level with a new one"),
+    ("seed", "seed: show the game's seed"),
+];
+
+/// A text console for running wizard commands against the live game, opened with `~`.
+///
+/// Results go into the message log, the tail of which is shown above the
+/// prompt. Up and Down step through past commands and Tab completes
+/// command names.
+pub struct ConsoleState {
+    game: Option<Game>,
+    input: String,
+    /// Where we are in the game's console history while stepping through it
+    history_index: Option<usize>,
+    done: bool,
+}
+
+/// Opens the console
+pub fn open() -> Box<dyn State> {
+    box ConsoleState { game: None, input: String::new(), history_index: None, done: false }
+}
+
+impl ConsoleState {
+    /// Runs the command typed into the console
+    fn submit(&mut self) {
+        let line = self.input.trim().to_string();
+        self.input.clear();
+        self.history_index = None;
+
+        if line.is_empty() {
+            return
+        }
+
+        let game = self.game.as_mut().unwrap();
+        game.console_history.push(line.clone());
+        game.message_cache.push(format!("> {}", line));
+
+        let output = match run(game, &line) {
+            Ok(lines) => lines,
+            Err(e) => vec![e],
+        };
+        game.message_cache.extend(output);
+    }
+
+    /// Completes the command name being typed, or lists the candidates if there's more than one
+    fn complete(&mut self) {
+        if self.input.contains(' ') {
+            return
+        }
+
+        let candidates = COMMANDS.iter().map(|&(name, _)| name).filter(|name| name.starts_with(self.input.as_str())).collect::<Vec<_>>();
+        match candidates.len() {
+            0 => {}
+            1 => self.input = format!("{} ", candidates[0]),
+            _ => {
+                let game = self.game.as_mut().unwrap();
+                game.message_cache.push(candidates.join(" "));
+            }
+        }
+    }
+
+    /// Steps back (or forward) through the history
+    fn recall(&mut self, back: bool) {
+        let history = &self.game.as_ref().unwrap().console_history;
+        if history.is_empty() {
+            return
+        }
+
+        let index = match (self.history_index, back) {
+            (None, true) => Some(history.len() - 1),
+            (None, false) => None,
+            (Some(i), true) => Some(if i > 0 { i - 1 } else { 0 }),
+            (Some(i), false) => if i + 1 < history.len() { Some(i + 1) } else { None },
+        };
+
+        self.history_index = index;
+        self.input = index.map_or(String::new(), |i| history[i].clone());
+    }
+}
+
+impl State for ConsoleState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> { None }
+    fn maybe_exit_game(&self) -> Option<Exit> { None }
+    fn should_exit(&self) -> bool { self.done }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        let keypress = self.game.as_mut().unwrap().wait_for_keypress();
+        match keypress.code {
+            KeyCode::Escape => self.done = true,
+            KeyCode::Enter | KeyCode::NumPadEnter => self.submit(),
+            KeyCode::Backspace => { self.input.pop(); }
+            KeyCode::Tab => self.complete(),
+            KeyCode::Up => self.recall(true),
+            KeyCode::Down => self.recall(false),
+            _ => if keypress.printable != '\0' && !keypress.printable.is_control() {
+                self.input.push(keypress.printable);
+            }
+        }
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        game.rendering_component.before_render_new_frame();
+        game.render();
+
+        // Blank out the bottom of the screen and draw the log and prompt over it
+        let width = game.window_bounds.max.x as usize - 1;
+        let bottom = game.window_bounds.max.y - 2;
+        let shown = game.message_cache.iter().rev().take(CONSOLE_LINES).rev().cloned().collect::<Vec<_>>();
+        let top = bottom - CONSOLE_LINES as i32;
+
+        for i in 0..CONSOLE_LINES {
+            let line = shown.get(i).map_or("", |l| l.as_str());
+            game.rendering_component.print(&format!("{:<1$.1$}", line, width), 0, top + i as i32);
+        }
+        game.rendering_component.print(&format!("{:<1$.1$}", format!("> {}_", self.input), width), 0, bottom);
+
+        game.rendering_component.after_render_new_frame();
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
+
+/// Runs a console command, returning the lines to print
+fn run(game: &mut Game, line: &str) -> Result<Vec<String>, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let (name, args) = (words[0], &words[1..]);
+
+    let usage = || {
+        let (_, usage) = COMMANDS.iter().find(|&&(command, _)| command == name).unwrap();
+        format!("Usage: {}", usage)
+    };
+
+    match (name, args.len()) {
+        ("help", 0) => Ok(COMMANDS.iter().map(|&(_, usage)| usage.to_string()).collect()),
+        ("spawn", n) if n > 0 => {
+            // The monster's name may have spaces in it, so look for coordinates on the end
+            let at = if n > 2 {
+                match (args[n - 2].parse::<i32>(), args[n - 1].parse::<i32>()) {
+                    (Ok(x), Ok(y)) => Some(Point { x, y }),
+                    _ => None,
+                }
+            } else {
+                None
+            };
+
+            match at {
+                Some(at) => wizard::spawn_monster_at(game, &args[..n - 2].join(" "), at),
+                None => {
+                    let near = game.level.entities[0].as_ref().unwrap().get_position();
+                    wizard::spawn_monster(game, &args.join(" "), near)
+                }
+            }.map(|m| vec![m])
+        }
+        ("give", n) if n > 0 => {
+            // Like monster names, item names may have spaces in them
+            let (name, quantity) = match args[n - 1].parse::<u32>() {
+                Ok(quantity) if n > 1 => (args[..n - 1].join(" "), quantity),
+                _ => (args.join(" "), 1),
+            };
+            wizard::give_item(game, &name, quantity).map(|m| vec![m])
+        }
+        ("tp", 2) => {
+            let to = Point { x: number(args[0])?, y: number(args[1])? };
+            wizard::teleport(game, to).map(|m| vec![m])
+        }
+        ("heal", 0) => wizard::heal(game).map(|m| vec![m]),
+        ("depth", 1) => wizard::goto_depth(game, number(args[0])?).map(|m| vec![m]),
+        ("dump", 1) => match args[0] {
+            "entities" => Ok(dump_entities(game)),
+            "items" => Ok(dump_items(game)),
+            _ => Err(usage()),
+        }
+        ("regen", 1) if args[0] == "level" => {
+            game.regen_level()?;
+            Ok(vec!["The level shifts around you".to_string()])
+        }
+        ("seed", 0) => Ok(vec![format!("Seed: {}", game.seed)]),
+        _ => if COMMANDS.iter().any(|&(command, _)| command == name) {
+            Err(usage())
+        } else {
+            Err(format!("Unknown command '{}', try 'help'", name))
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse::<T>().map_err(|_| format!("'{}' isn't a number", word))
+}
+
+fn dump_entities(game: &Game) -> Vec<String> {
+    game.level.entities.iter().enumerate().map(|(i, e)| {
+        let e = e.as_ref().unwrap();
+        let position = e.get_position();
+        format!("{}: {} '{}' at ({}, {}), {}/{} hp, {:?}", i, e.name, e.display_char, position.x, position.y, e.health, e.max_health, e.alertness)
+    }).collect()
+}
+
+fn dump_items(game: &Game) -> Vec<String> {
+    let mut positions = game.level.items.keys().cloned().collect::<Vec<_>>();
+    positions.sort();
+
+    positions.iter().flat_map(|position| {
+        game.level.items[position].iter().map(move |item| format!("{} at ({}, {})", item.describe(), position.x, position.y))
+    }).collect()
+}
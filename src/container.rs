@@ -0,0 +1,136 @@
+use crate::game::Game;
+use crate::state::{State, MenuState, TwoPaneSelectState};
+use crate::actor::Actor;
+use crate::keymap::Command;
+use crate::identify::Discoveries;
+use crate::item::{self, Item};
+use crate::level::Level;
+use crate::util::{capitalize, uncapitalize};
+
+/// Where a container the player is opening is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Container {
+    /// At this place in the pile the `Entity` is standing on
+    Floor(usize),
+    /// At this place in the `Entity`'s pack
+    Pack(usize),
+}
+
+/// How a container is referred to in the middle of a message, e.g. "the chest"
+pub fn name(container: &Item, discoveries: &Discoveries) -> String {
+    format!("the {}", uncapitalize(&discoveries.name_of(container)))
+}
+
+/// Takes the container out of where it is, so it can be opened without borrowing the
+/// pile or pack it's in. It has to be put back with `put_back`.
+pub fn lift(level: &mut Level, target: usize, container: Container) -> Option<Item> {
+    match container {
+        Container::Floor(at) => {
+            let position = level.entities[target].as_ref().unwrap().get_position();
+            let pile = level.items.get_mut(&position)?;
+            if at < pile.len() && pile[at].is_container() { Some(pile.remove(at)) } else { None }
+        }
+        Container::Pack(at) => {
+            let pack = &mut level.entities[target].as_mut().unwrap().inventory;
+            if at < pack.len() && pack[at].is_container() { Some(pack.remove(at)) } else { None }
+        }
+    }
+}
+
+/// Puts a lifted container back where it came from
+pub fn put_back(level: &mut Level, target: usize, container: Container, item: Item) {
+    match container {
+        Container::Floor(at) => {
+            let position = level.entities[target].as_ref().unwrap().get_position();
+            let pile = level.items.entry(position).or_insert_with(Vec::new);
+            pile.insert(at.min(pile.len()), item);
+        }
+        Container::Pack(at) => {
+            let pack = &mut level.entities[target].as_mut().unwrap().inventory;
+            pack.insert(at.min(pack.len()), item);
+        }
+    }
+}
+
+/// Every container the player can open: those underfoot, then those in the pack
+fn containers(game: &Game) -> Vec<Container> {
+    let player = game.level.entities[0].as_ref().unwrap();
+    let pile = game.level.items.get(&player.get_position()).map_or(&[][..], |pile| pile.as_slice());
+
+    let floor = (0..pile.len()).filter(|&i| pile[i].is_container()).map(Container::Floor);
+    let pack = (0..player.inventory.len()).filter(|&i| player.inventory[i].is_container()).map(Container::Pack);
+    floor.chain(pack).collect()
+}
+
+fn get(game: &Game, container: Container) -> &Item {
+    match container {
+        Container::Floor(at) => {
+            let position = game.level.entities[0].as_ref().unwrap().get_position();
+            &game.level.items[&position][at]
+        }
+        Container::Pack(at) => &game.level.entities[0].as_ref().unwrap().inventory[at],
+    }
+}
+
+/// Opens the container the player is standing on or carrying, asking which if there's more than one
+pub fn open_menu(game: &mut Game) -> Option<Box<dyn State>> {
+    let containers = containers(game);
+    match containers.len() {
+        0 => {
+            game.game_log("You have nothing to open".to_string());
+            None
+        }
+        1 => open(game, containers[0]),
+        _ => {
+            let names = containers.iter().map(|&c| {
+                let item = get(game, c);
+                let place = if let Container::Floor(_) = c { "here" } else { "in your pack" };
+                format!("{} ({})", item.describe_to(&game.level.discoveries), place)
+            }).collect();
+
+            Some(box MenuState::new("Open what?", names, box move |game, choice| {
+                open(game, containers[choice])
+            }))
+        }
+    }
+}
+
+/// Shows what's in the container beside what can be put in it from the pack. Moving
+/// things takes the player's turn once the menu closes, as does unlocking the container
+/// if it's locked and the player has a key.
+fn open(game: &mut Game, container: Container) -> Option<Box<dyn State>> {
+    let item = get(game, container);
+    let name = name(item, &game.level.discoveries);
+    let player = game.level.entities[0].as_ref().unwrap();
+
+    if item.locked {
+        if player.inventory.iter().any(|i| i.name == item::KEY) {
+            game.level.open_choice = Some((container, vec![], vec![]));
+            game.level.command = Some(Command::Open);
+        } else {
+            game.game_log(format!("{} is locked", capitalize(&name)));
+        }
+        return None
+    }
+
+    // Containers don't go in containers, and what's worn or owed for stays in the pack
+    let storable = (0..player.inventory.len()).filter(|&i| {
+        let i = &player.inventory[i];
+        !i.is_container() && !i.worn && !i.unpaid
+    }).collect::<Vec<_>>();
+
+    if item.contents.is_empty() && storable.is_empty() {
+        game.game_log(format!("{} is empty, and you have nothing to put in it", capitalize(&name)));
+        return None
+    }
+
+    let contents = item.contents.iter().map(|i| i.describe_to(&game.level.discoveries)).collect();
+    let pack = storable.iter().map(|&i| player.inventory[i].describe_to(&game.level.discoveries)).collect();
+
+    let title = format!("In {}", name);
+    Some(box TwoPaneSelectState::new(&title, contents, "In your pack", pack, box move |game, take, put| {
+        let put = put.into_iter().map(|i| storable[i]).collect();
+        game.level.open_choice = Some((container, take, put));
+        game.level.command = Some(Command::Open);
+    }))
+}
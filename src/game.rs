@@ -1,10 +1,23 @@
 use crate::level::Level;
-use crate::util::{Point, Bound};
-use crate::rendering::{RenderingComponent, TcodRenderingComponent};
+use crate::map::Generator;
+use crate::util::{Point, Bound, capitalize};
+use crate::rendering::{RenderingComponent, TcodRenderingComponent, HeadlessRenderingComponent};
+use crate::replay::{Replay, Playback};
+use crate::keymap::Keymap;
+use crate::save::{self, Slot, SaveData};
+use crate::item::pile_symbol;
+use crate::actor::Encumbrance;
+use crate::identify::Discoveries;
+use crate::monster::Catalogue;
 
-use tcod::input::Key;
-use rand_isaac::IsaacRng;
-use rand_core::{SeedableRng, RngCore};
+use crate::rng::RngStreams;
+
+use std::time::Duration;
+use std::thread::{self, JoinHandle};
+use std::io::{ErrorKind, Error as IOE};
+
+use tcod::input::{Key, KeyCode};
+use rand_core::RngCore;
 
 /// The y offset of the map from the top
 pub const MAP_OFFSET: i32 = 2; // 1 line for messages, one for padding
@@ -12,8 +25,27 @@ pub const MAP_OFFSET: i32 = 2; // 1 line for messages, one for padding
 pub const MAP_WIDTH: i32 = 80;
 /// The height of the map display area
 pub const MAP_HEIGHT: i32 = 50;
+/// How many turns go by between autosaves
+pub const AUTOSAVE_TURNS: u32 = 100;
 
-pub const SHOW_MAP: bool = true;
+/// Everything about a new game that can be chosen before it starts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameSettings {
+    /// The width of the map
+    pub width: i32,
+    /// The height of the map
+    pub height: i32,
+    /// How levels are laid out
+    pub generator: Generator,
+    /// Whether debug mode is on: the whole map is shown, along with what monsters are planning
+    pub wizard: bool,
+}
+
+impl Default for GameSettings {
+    fn default() -> GameSettings {
+        GameSettings { width: MAP_WIDTH, height: MAP_HEIGHT, generator: Generator::Arena, wizard: false }
+    }
+}
 
 /// Game struct containing all the information about the current game state
 pub struct Game {
@@ -25,63 +57,282 @@ pub struct Game {
     pub rendering_component: Box<dyn RenderingComponent + 'static>,
     /// A `Level` struct containing all the information on the current level
     pub level: Level,
-    /// The game's RNG
-    pub random: IsaacRng,
-    /// The game's RNG seed
+    /// The game's RNG seed, from which every random stream is derived
     pub seed: u64,
+    /// The settings the game was started with
+    pub settings: GameSettings,
+    /// How deep in the dungeon the player is, starting at 1
+    pub depth: u32,
+    /// Whether the whole map is drawn, seen or not (wizard mode)
+    pub show_map: bool,
     /// A vector of messages to show to the player
     pub message_queue: Vec<String>,
     /// Where we are in the massages vector
     pub message_cache: Vec<String>,
+    /// Translates the player's keypresses into commands
+    pub keymap: Keymap,
+    /// The monsters levels are filled with, loaded once when the game starts
+    pub monsters: Catalogue,
+    /// Every key the player has pressed this game
+    pub recording: Replay,
+    /// The replay being played back, if any
+    pub playback: Option<Playback>,
+    /// Whether the game is a replay, which leaves no mark on the high scores
+    pub replaying: bool,
+    /// Where the game is saved to, if anywhere
+    pub save_slot: Option<Slot>,
+    /// Whether the game is catching up with an autosave or save, after which the player takes over
+    pub resuming: bool,
+    /// The turn of the last autosave
+    last_autosave: u32,
+    /// The autosave being written in the background
+    autosaving: Option<JoinHandle<std::io::Result<()>>>,
+    /// Commands entered into the developer console, oldest first
+    pub console_history: Vec<String>,
+    /// How many rounds have been played
+    pub turns: u32,
+    /// The names of the monsters the player has killed, in order
+    pub kills: Vec<String>,
 }
 
 impl Game {
     /// Creates a new game struct complete with a first level and rendering component
-    pub fn new(old_seed: Option<u64>) -> Game {
+    pub fn new(old_seed: Option<u64>, settings: GameSettings, keymap: Keymap, monsters: Catalogue) -> Game {
+        Self::build(Self::init_seed(old_seed), settings, keymap, monsters, None)
+    }
+
+    /// Creates a game that plays back `replay`, waiting `delay` between keys.
+    /// A headless game never opens a window and ends when the replay runs out.
+    /// The replay has to have been played with `monsters`.
+    pub fn from_replay(replay: Replay, monsters: Catalogue, delay: Duration, headless: bool) -> Result<Game, String> {
+        replay.check_monsters(&monsters)?;
+        let (seed, settings, keymap) = (replay.seed, replay.settings, replay.keymap.clone());
+        Ok(Self::build(seed, settings, keymap, monsters, Some(Playback::new(replay, delay, headless))))
+    }
+
+    fn build(seed: u64, settings: GameSettings, keymap: Keymap, monsters: Catalogue, playback: Option<Playback>) -> Game {
         let bounds = Bound {
             min: Point { x: 0, y: 0 },
-            max: Point { x: MAP_WIDTH, y: MAP_HEIGHT + MAP_OFFSET },
+            max: Point { x: settings.width, y: settings.height + MAP_OFFSET },
         };
 
-        let (mut isaac, seed) = Self::init_rng(old_seed);
+        let mut level = Self::init_level(&settings, &monsters, seed);
+        level.show_ai = settings.wizard;
 
-        let level = Self::init_level(&mut isaac);
+        let headless = playback.as_ref().map_or(false, |p| p.headless);
+        let rc = Self::init_renderer(bounds, &level, &settings, headless);
 
-        let rc = Self::init_renderer(bounds, &level);
-        
         Game {
             seed,
             level,
             exit: false,
             window_bounds: bounds,
             rendering_component: rc,
-            random: isaac,
             message_queue: vec!["Welcome to MR: TOM".to_string()],
             message_cache: vec![],
+            settings,
+            depth: 1,
+            show_map: settings.wizard,
+            recording: Replay::new(seed, settings, &monsters, keymap.clone()),
+            keymap,
+            monsters,
+            replaying: playback.is_some(),
+            save_slot: None,
+            resuming: false,
+            last_autosave: 0,
+            autosaving: None,
+            playback,
+            console_history: vec![],
+            turns: 0,
+            kills: vec![],
         }
     }
 
-    fn init_renderer(bounds: Bound, level: &Level) -> Box<dyn RenderingComponent + 'static> {
-        box TcodRenderingComponent::new(bounds, &level.map_component)
+    fn init_renderer(bounds: Bound, level: &Level, settings: &GameSettings, headless: bool) -> Box<dyn RenderingComponent + 'static> {
+        if headless {
+            box HeadlessRenderingComponent
+        } else {
+            box TcodRenderingComponent::new(bounds, &level.map_component, settings.wizard)
+        }
     }
 
-    fn init_level(random: &mut IsaacRng) -> Level {
-        Level::new(MAP_WIDTH, MAP_HEIGHT, random, None)
+    /// The first level of the game with the given seed
+    fn init_level(settings: &GameSettings, monsters: &Catalogue, seed: u64) -> Level {
+        Level::new(settings.width, settings.height, settings.generator, &monsters.templates, RngStreams::for_depth(seed, 1), Discoveries::new(seed), None)
     }
 
-    fn init_rng(old_seed: Option<u64>) -> (IsaacRng, u64) {
+    /// Uses the saved seed if there is one, else picks a fresh one.
+    /// This is the only place gameplay doesn't come from the seeded streams.
+    fn init_seed(old_seed: Option<u64>) -> u64 {
         if let Some(s) = old_seed {
-            (IsaacRng::seed_from_u64(s), s)
+            s
         } else {
-            let mut rng = rand::thread_rng();
-            let new_seed = rng.next_u64();
-            (IsaacRng::seed_from_u64(new_seed), new_seed)
+            rand::thread_rng().next_u64()
+        }
+    }
+
+    /// Throws the game away for a fresh one from `old_seed` (or a new seed), keeping the window.
+    /// Used by the main menu, which has a game behind it before one is picked.
+    pub fn restart(&mut self, old_seed: Option<u64>) {
+        let seed = Self::init_seed(old_seed);
+
+        self.level = Self::init_level(&self.settings, &self.monsters, seed);
+        self.level.show_ai = self.settings.wizard;
+        self.rendering_component.set_map(&self.level.map_component);
+
+        self.show_map = self.settings.wizard;
+        self.rendering_component.set_show_map(self.show_map);
+
+        self.seed = seed;
+        self.depth = 1;
+        self.recording = Replay::new(seed, self.settings, &self.monsters, self.keymap.clone());
+        self.console_history.clear();
+        self.turns = 0;
+        self.last_autosave = 0;
+        self.kills.clear();
+    }
+
+    /// Restarts the game as it was recorded in `replay`, and plays the keys back
+    /// without showing them until the game has caught up. The map has to fit the window,
+    /// and the monsters have to be the ones it was played with.
+    pub fn resume(&mut self, replay: Replay) -> Result<(), String> {
+        replay.check_monsters(&self.monsters)?;
+        let (width, height) = (replay.settings.width, replay.settings.height);
+        if width != self.settings.width || height != self.settings.height {
+            return Err(format!("the game was played on a {}x{} map; start with --width {} --height {} to resume it",
+                width, height, width, height))
         }
+
+        self.settings = replay.settings;
+        self.keymap = replay.keymap.clone();
+        self.restart(Some(replay.seed));
+
+        self.playback = Some(Playback::new(replay, Duration::from_millis(0), false));
+        self.resuming = true;
+        self.rendering_component.set_fast_forward(true);
+        Ok(())
+    }
+
+    /// Moves the player down (or up) to a freshly made level at `depth`
+    pub fn goto_depth(&mut self, depth: u32) -> Result<(), String> {
+        self.replace_level(RngStreams::for_depth(self.seed, depth))?;
+        self.depth = depth;
+        self.autosave();
+        Ok(())
+    }
+
+    /// Throws away the current level for a new one at the same depth
+    pub fn regen_level(&mut self) -> Result<(), String> {
+        // Seeded from the old level, so replays regenerate the same way
+        let rng = RngStreams::from_seed(self.level.rng.mapgen.next_u64());
+        self.replace_level(rng)
+    }
+
+    /// Swaps the current level for a new one made from `rng`, bringing the player and what they've learned along.
+    /// The player can't leave while they owe the level's shop anything.
+    fn replace_level(&mut self, rng: RngStreams) -> Result<(), String> {
+        let bill = self.level.entities[0].as_ref().unwrap().bill();
+        if bill > 0 {
+            return Err(format!("You owe the shopkeeper {} gold, and can't leave without paying", bill))
+        }
+
+        let player = self.level.entities[0].take();
+
+        let discoveries = self.level.discoveries.clone();
+        let mut level = Level::new(self.settings.width, self.settings.height, self.settings.generator, &self.monsters.templates, rng, discoveries, player);
+        level.show_ai = self.level.show_ai;
+
+        self.level = level;
+        self.rendering_component.set_map(&self.level.map_component);
+        Ok(())
+    }
+
+    /// Turns on wizard mode for the rest of the game
+    pub fn enable_wizard(&mut self) {
+        self.settings.wizard = true;
+    }
+
+    /// Shows or hides the parts of the map the player hasn't seen
+    pub fn toggle_show_map(&mut self) {
+        self.show_map = !self.show_map;
+        self.rendering_component.set_show_map(self.show_map);
+    }
+
+    /// Shows or hides the paths monsters are planning to take
+    pub fn toggle_show_ai(&mut self) {
+        self.level.show_ai = !self.level.show_ai;
     }
 
     /// Calls the update methods of all objects in the domain of the game. Think player, items, mobs, etc.
     pub fn update(&mut self) {
+        self.take_turns();
+        self.message_queue.extend(self.level.messages.drain(..));
+        self.kills.extend(self.level.kills.drain(..));
+
+        if self.turns >= self.last_autosave + AUTOSAVE_TURNS {
+            self.autosave();
+        }
+    }
+
+    /// Writes the game to its slot's autosave in the background. Nothing is
+    /// saved while a replay plays, or while a resumed game is catching up.
+    pub fn autosave(&mut self) {
+        if self.playback.is_some() {
+            return
+        }
+        let path = match &self.save_slot {
+            Some(slot) => slot.autosave_path(),
+            None => return,
+        };
+
+        // Only one autosave is written at a time
+        if let Err(e) = self.finish_autosave() {
+            self.game_log(format!("Could not autosave: {}", e.to_string()));
+        }
+
+        let contents = save::encode(&SaveData::new(self));
+        self.autosaving = Some(thread::spawn(move || save::write_atomically(&path, &contents)));
+        self.last_autosave = self.turns;
+    }
+
+    /// Waits for the autosave being written, if there is one
+    pub fn finish_autosave(&mut self) -> std::io::Result<()> {
+        match self.autosaving.take() {
+            Some(handle) => handle.join().unwrap_or_else(|_| Err(IOE::new(ErrorKind::Other, "the autosave was abandoned"))),
+            None => Ok(()),
+        }
+    }
+
+    /// Lets each entity act in turn, stopping early if the player has yet to decide on an action
+    fn take_turns(&mut self) {
+        loop {
+            self.take_round();
+
+            // A player still busy with a slow action sits the next round out while everything else acts
+            let player = self.level.entities[0].as_ref().unwrap();
+            if player.delay == 0 || player.is_dead() {
+                return
+            }
+        }
+    }
+
+    /// Plays out a round, or the rest of one, stopping early if the player has yet to decide on an action
+    fn take_round(&mut self) {
         while self.level.current_actor < self.level.entities.len() {
+            // Monsters killed earlier this round don't get to act
+            if self.level.current_actor != 0 && self.level.entities[self.level.current_actor].as_ref().unwrap().is_dead() {
+                self.level.current_actor += 1;
+                continue
+            }
+
+            // Anything still busy with a slow action loses its go
+            let entity = self.level.entities[self.level.current_actor].as_mut().unwrap();
+            if entity.delay > 0 {
+                entity.delay -= 1;
+                self.level.current_actor += 1;
+                continue
+            }
+
             let mut entity = self.level.entities[self.level.current_actor].take().unwrap();
             let mut action = entity.get_action(&mut self.level);
             self.level.entities[self.level.current_actor] = Some(entity);
@@ -104,36 +355,93 @@ impl Game {
                 }
                 self.level.current_actor += 1;
             } else {
-                println!("No action");
+                // The player hasn't decided on anything yet, such as after pressing an unbound key
                 return
             }
 
         }
 
+        self.level.remove_dead();
+        self.level.check_shop();
+        self.level.noises.clear();
+        self.level.pack_claims.clear();
         self.level.current_actor = 0;
+        self.turns += 1;
     }
 
     /// Delegates rendering of the map, mobs, and player to the `rendering_component` in the correct order
     pub fn render(&mut self) {
         self.level.map_component.render(&mut self.rendering_component, &self.level.entities[0].as_ref().unwrap());
 
-        for items in self.level.items.values() {
-            items[0].render(&mut self.rendering_component);
+        for (position, items) in self.level.items.iter() {
+            self.rendering_component.render_object(*position, pile_symbol(items));
         }
 
         // reverse to render the player last because it's always 0
         for i in self.level.entities.iter().rev() {
             i.as_ref().unwrap().render(&mut self.rendering_component);
         }
+
+        // How weighed down the player is and their gold go on the line under the messages
+        let player = self.level.entities[0].as_ref().unwrap();
+        let mut status = format!("Gold: {}", player.gold);
+        let encumbrance = player.encumbrance();
+        if encumbrance != Encumbrance::Unencumbered {
+            status = format!("{}  {}", capitalize(encumbrance.name()), status);
+        }
+        self.rendering_component.print(&status, self.window_bounds.max.x - 1 - status.len() as i32, MAP_OFFSET - 1);
     }
 
-    /// Receives the keypresses in the game loop
+    /// Receives the keypresses in the game loop, from the replay if one is playing.
+    /// Every key is recorded so the game can be replayed later.
     pub fn wait_for_keypress(&mut self) -> Key {
-        let ks = self.rendering_component.wait_for_keypress();
-        self.level.input = Some(ks);
+        let ks = match self.next_replayed_key() {
+            Some(key) => key,
+            None => self.rendering_component.wait_for_keypress(),
+        };
+
+        self.recording.keys.push(ks);
+        self.level.command = self.keymap.command_for(&ks);
         return ks;
     }
 
+    /// Takes the next key from the replay. Once it runs out a headless game
+    /// is told to exit, and a windowed one hands control to the player.
+    fn next_replayed_key(&mut self) -> Option<Key> {
+        let playback = self.playback.as_mut()?;
+        if let Some(key) = playback.next_key() {
+            // A resumed game is shown again from the frame after its last key
+            if self.resuming && playback.is_empty() {
+                self.finish_resuming();
+            }
+            return Some(key)
+        }
+
+        if playback.headless {
+            self.exit = true;
+            // Enter is the one key that dismisses every prompt on the way out
+            let mut enter = Key::default();
+            enter.code = KeyCode::Enter;
+            return Some(enter)
+        }
+
+        if self.resuming {
+            self.finish_resuming();
+        } else {
+            self.playback = None;
+            self.game_log("The replay has ended".to_string());
+        }
+        None
+    }
+
+    /// Hands a resumed game back to the player
+    fn finish_resuming(&mut self) {
+        self.playback = None;
+        self.resuming = false;
+        self.rendering_component.set_fast_forward(false);
+        self.game_log("Welcome back".to_string());
+    }
+
     pub fn game_log(&mut self, message: String) {
         self.message_queue.push(message);
     }
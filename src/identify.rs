@@ -0,0 +1,108 @@
+use crate::item::{self, Item, ItemType};
+use crate::rng;
+use crate::util::capitalize;
+
+use hashbrown::{HashMap, HashSet};
+use rand::seq::SliceRandom;
+
+/// What unidentified potions can look like
+const POTION_APPEARANCES: [&str; 8] = ["murky", "fizzy", "smoky", "bubbly", "cloudy", "milky", "golden", "violet"];
+/// What unidentified scrolls can be labeled
+const SCROLL_LABELS: [&str; 8] = ["XYZZY", "FOOBIE BLETCH", "ZELGO MER", "ELBIB YLOH", "JUYED AWK YACC", "NR 9", "PRATYAVAYAH", "VERR YED HORRE"];
+/// What unidentified rings can be made of
+const RING_APPEARANCES: [&str; 8] = ["jade", "ruby", "wooden", "iron", "opal", "coral", "twisted", "onyx"];
+/// What unidentified wands can be made of
+const WAND_APPEARANCES: [&str; 8] = ["oak", "bone", "copper", "glass", "crystal", "ebony", "pine", "tin"];
+
+/// What the player has found out about the kinds of item that don't give
+/// themselves away: potions, scrolls, rings and wands.
+///
+/// Each game shuffles what those kinds look like, from its seed, so a murky
+/// potion might heal in one game and sicken in the next. Kinds are known by
+/// their true names here.
+#[derive(Clone)]
+pub struct Discoveries {
+    /// What each kind looks like until it's identified
+    appearances: HashMap<&'static str, String>,
+    /// The kinds the player has identified
+    known: HashSet<String>,
+    /// What the player has called kinds they have yet to identify
+    called: HashMap<String, String>,
+}
+
+impl Discoveries {
+    /// Deals out the appearances for the game with the given seed
+    pub fn new(seed: u64) -> Discoveries {
+        let mut random = rng::appearances(seed);
+        let mut appearances = HashMap::new();
+
+        for &(item_type, pool) in &[
+            (ItemType::POTION, &POTION_APPEARANCES), (ItemType::SCROLL, &SCROLL_LABELS),
+            (ItemType::RING, &RING_APPEARANCES), (ItemType::WAND, &WAND_APPEARANCES),
+        ] {
+            let mut pool = pool.to_vec();
+            pool.shuffle(&mut random);
+
+            let kinds = item::catalogue().iter().filter(|t| t.item_type == item_type);
+            for (template, word) in kinds.zip(pool) {
+                appearances.insert(template.name, appearance(item_type, word));
+            }
+        }
+
+        Discoveries { appearances, known: HashSet::new(), called: HashMap::new() }
+    }
+
+    /// What the kind called `name` looks like, if it has to be identified
+    pub fn appearance(&self, name: &str) -> Option<&str> {
+        self.appearances.get(name).map(|a| a.as_str())
+    }
+
+    /// Whether the player knows what the kind called `name` is
+    pub fn is_known(&self, name: &str) -> bool {
+        !self.appearances.contains_key(name) || self.known.contains(name)
+    }
+
+    /// Identifies the kind called `name`, returning whether it was unknown until now
+    pub fn identify(&mut self, name: &str) -> bool {
+        if self.is_known(name) {
+            return false
+        }
+
+        self.called.remove(name);
+        self.known.insert(name.to_string());
+        true
+    }
+
+    /// Gives the unidentified kind called `name` a name of the player's own, or forgets it if `called` is blank
+    pub fn call(&mut self, name: &str, called: &str) {
+        if called.trim().is_empty() {
+            self.called.remove(name);
+        } else {
+            self.called.insert(name.to_string(), called.trim().to_string());
+        }
+    }
+
+    /// The name of the item's kind as the player knows it, e.g. "Potion of healing"
+    /// or "Murky potion called fizzy"
+    pub fn name_of(&self, item: &Item) -> String {
+        if self.is_known(&item.name) {
+            return item.name.clone()
+        }
+
+        let appearance = capitalize(&self.appearances[item.name.as_str()]);
+        match self.called.get(&item.name) {
+            Some(called) => format!("{} called {}", appearance, called),
+            None => appearance,
+        }
+    }
+}
+
+/// How an unidentified item of `item_type` with the given word is described, e.g. "murky potion"
+fn appearance(item_type: ItemType, word: &str) -> String {
+    match item_type {
+        ItemType::SCROLL => format!("scroll labeled {}", word),
+        ItemType::RING => format!("{} ring", word),
+        ItemType::WAND => format!("{} wand", word),
+        _ => format!("{} potion", word),
+    }
+}
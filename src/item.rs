@@ -1,9 +1,11 @@
 use crate::map::Rect;
-use crate::util::Point;
+use crate::util::{Point, uncapitalize};
 use crate::rendering::RenderingComponent;
+use crate::identify::Discoveries;
 
 use hashbrown::HashMap;
 
+use rand::Rng;
 use rand_isaac::IsaacRng;
 
 // These constants shamelessly copy/pasted from NetHack source code
@@ -24,10 +26,13 @@ const ROCK_SYM: char = '`';
 const BALL_SYM: char = '0';
 const CHAIN_SYM: char = '_';
 const VENOM_SYM: char = '.';
+/// Drawn where more than one kind of item lies on a tile
+const PILE_SYM: char = '&';
 
 pub type ItemsMap = HashMap<Point, Vec<Item>>;
 
 // Also shamelessly copy/pasted
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ItemType {
     ILLOBJ,
     WEAPON,
@@ -48,11 +53,150 @@ pub enum ItemType {
     VENOM,
 }
 
+/// How heavy a gold piece is
+pub const COIN_WEIGHT: u32 = 1;
+/// What chests are called, which can't be picked up and may be locked
+pub const CHEST: &str = "Chest";
+/// What keys are called, which unlock chests
+pub const KEY: &str = "Key";
+/// How much strength a ring of strength gives, or a ring of weakness takes away
+const RING_STRENGTH: i32 = 5;
+
+/// Every item that can be made by name
+const CATALOGUE: [ItemTemplate; 18] = [
+    ItemTemplate { name: "Sword", item_type: ItemType::WEAPON, lit: false, stackable: false, weight: 40, price: 15, effect: None, container: false },
+    ItemTemplate { name: "Torch", item_type: ItemType::TOOL, lit: true, stackable: false, weight: 20, price: 5, effect: None, container: false },
+    ItemTemplate { name: "Gold piece", item_type: ItemType::COIN, lit: false, stackable: true, weight: COIN_WEIGHT, price: 1, effect: None, container: false },
+    ItemTemplate { name: "Arrow", item_type: ItemType::WEAPON, lit: false, stackable: true, weight: 1, price: 2, effect: None, container: false },
+    ItemTemplate { name: "Potion of healing", item_type: ItemType::POTION, lit: false, stackable: true, weight: 20, price: 20, effect: Some(Effect::Heal), container: false },
+    ItemTemplate { name: "Potion of strength", item_type: ItemType::POTION, lit: false, stackable: true, weight: 20, price: 50, effect: Some(Effect::GainStrength), container: false },
+    ItemTemplate { name: "Potion of sickness", item_type: ItemType::POTION, lit: false, stackable: true, weight: 20, price: 10, effect: Some(Effect::Sickness), container: false },
+    ItemTemplate { name: "Scroll of identify", item_type: ItemType::SCROLL, lit: false, stackable: true, weight: 5, price: 10, effect: Some(Effect::Identify), container: false },
+    ItemTemplate { name: "Scroll of teleportation", item_type: ItemType::SCROLL, lit: false, stackable: true, weight: 5, price: 25, effect: Some(Effect::Teleport), container: false },
+    ItemTemplate { name: "Scroll of magic mapping", item_type: ItemType::SCROLL, lit: false, stackable: true, weight: 5, price: 30, effect: Some(Effect::MagicMapping), container: false },
+    ItemTemplate { name: "Ring of strength", item_type: ItemType::RING, lit: false, stackable: false, weight: 3, price: 60, effect: Some(Effect::Strength(RING_STRENGTH)), container: false },
+    ItemTemplate { name: "Ring of weakness", item_type: ItemType::RING, lit: false, stackable: false, weight: 3, price: 60, effect: Some(Effect::Strength(-RING_STRENGTH)), container: false },
+    ItemTemplate { name: "Ring of searching", item_type: ItemType::RING, lit: false, stackable: false, weight: 3, price: 40, effect: Some(Effect::Searching), container: false },
+    ItemTemplate { name: "Wand of striking", item_type: ItemType::WAND, lit: false, stackable: false, weight: 7, price: 50, effect: Some(Effect::Striking), container: false },
+    ItemTemplate { name: "Wand of teleportation", item_type: ItemType::WAND, lit: false, stackable: false, weight: 7, price: 60, effect: Some(Effect::TeleportOther), container: false },
+    ItemTemplate { name: CHEST, item_type: ItemType::TOOL, lit: false, stackable: false, weight: 350, price: 20, effect: None, container: true },
+    ItemTemplate { name: "Bag", item_type: ItemType::TOOL, lit: false, stackable: false, weight: 15, price: 15, effect: None, container: true },
+    ItemTemplate { name: KEY, item_type: ItemType::TOOL, lit: false, stackable: false, weight: 3, price: 10, effect: None, container: false },
+];
+
+/// How many gold pieces are left lying in a room, at least and at most
+const GOLD_PILE: (u32, u32) = (5, 30);
+/// How many arrows are left lying in a room, at least and at most
+const ARROW_BUNDLE: (u32, u32) = (3, 12);
+/// The chance of a level having a chest, and of that chest being locked
+const CHEST_CHANCE: f64 = 1.0 / 2.0;
+const LOCKED_CHANCE: f64 = 1.0 / 2.0;
+/// How many things are put in a chest, at least and at most
+const CHEST_LOOT: (u32, u32) = (1, 3);
+/// The chance of a level having a bag lying about
+const BAG_CHANCE: f64 = 1.0 / 4.0;
+
+/// What using an item does. Potions are drunk, scrolls read, rings put on and wands zapped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    /// Restores some health
+    Heal,
+    /// Adds a point of strength for good
+    GainStrength,
+    /// Takes away some health, though never the last of it
+    Sickness,
+    /// Identifies a kind of item being carried
+    Identify,
+    /// Moves the reader somewhere else on the level
+    Teleport,
+    /// Shows the reader the whole level
+    MagicMapping,
+    /// Adds to the wearer's strength, or takes away from it
+    Strength(i32),
+    /// Makes the wearer better at finding hidden traps
+    Searching,
+    /// Hurts the first thing in the way of the zap
+    Striking,
+    /// Moves the first thing in the way of the zap somewhere else on the level
+    TeleportOther,
+}
+
+/// A kind of item, from which any number of `Item`s can be made
+pub struct ItemTemplate {
+    pub name: &'static str,
+    pub item_type: ItemType,
+    /// Whether the item starts out burning
+    pub lit: bool,
+    /// Whether items of this kind are carried and left lying in stacks
+    pub stackable: bool,
+    /// How heavy one of this kind is
+    pub weight: u32,
+    /// How much gold one of this kind costs in a shop
+    pub price: u32,
+    /// What using one does, if it can be used
+    pub effect: Option<Effect>,
+    /// Whether other items can be put in this kind
+    pub container: bool,
+}
+
+impl ItemTemplate {
+    /// Makes a new item of this kind at `position`
+    pub fn spawn(&self, position: Point) -> Item {
+        self.spawn_stack(position, 1)
+    }
+
+    /// Makes `quantity` items of this kind at `position`, as one stack if they stack
+    pub fn spawn_stack(&self, position: Point, quantity: u32) -> Item {
+        Item {
+            position,
+            item_type: self.item_type,
+            name: self.name.to_string(),
+            lit: self.lit,
+            stackable: self.stackable,
+            quantity,
+            weight: self.weight,
+            worn: false,
+            for_sale: false,
+            unpaid: false,
+            contents: vec![],
+            locked: false,
+        }
+    }
+}
+
+/// Every kind of item there is
+pub fn catalogue() -> &'static [ItemTemplate] {
+    &CATALOGUE
+}
+
+/// Looks up a kind of item by name, ignoring case
+pub fn find(name: &str) -> Option<&'static ItemTemplate> {
+    CATALOGUE.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+}
+
 /// Struct representing a single item on the map
 pub struct Item {
     pub position: Point,
     pub item_type: ItemType,
     pub name: String,
+    /// Whether the item is a light source that is currently burning
+    pub lit: bool,
+    /// Whether the item stacks with others of its kind
+    pub stackable: bool,
+    /// How many there are in the stack. Items that don't stack are always one.
+    pub quantity: u32,
+    /// How heavy each one in the stack is
+    pub weight: u32,
+    /// Whether the item is a ring being worn
+    pub worn: bool,
+    /// Whether the item is a shop's stock, lying in the shop or in a chest there
+    pub for_sale: bool,
+    /// Whether the item was taken from a shop's stock and is still owed for
+    pub unpaid: bool,
+    /// What's inside the item, if it's a container
+    pub contents: Vec<Item>,
+    /// Whether the item is a container that has to be unlocked or forced before it can be opened
+    pub locked: bool,
 }
 
 impl Item {
@@ -60,6 +204,122 @@ impl Item {
         self.position
     }
 
+    /// How heavy the whole stack is, with whatever is inside it
+    pub fn weight(&self) -> u32 {
+        self.weight * self.quantity + self.contents.iter().map(|item| item.weight()).sum::<u32>()
+    }
+
+    /// Splits `quantity` off the stack as a stack of its own
+    pub fn split(&mut self, quantity: u32) -> Item {
+        self.quantity -= quantity;
+        Item {
+            position: self.position,
+            item_type: self.item_type,
+            name: self.name.clone(),
+            lit: self.lit,
+            stackable: self.stackable,
+            quantity,
+            weight: self.weight,
+            worn: false,
+            for_sale: self.for_sale,
+            unpaid: self.unpaid,
+            contents: vec![],
+            locked: false,
+        }
+    }
+
+    /// How much the whole stack costs in a shop, with whatever is inside it
+    pub fn price(&self) -> u32 {
+        self.own_price() + self.contents.iter().map(|item| item.price()).sum::<u32>()
+    }
+
+    /// How much the stack itself costs in a shop, leaving out whatever is inside it
+    pub fn own_price(&self) -> u32 {
+        find(&self.name).map_or(0, |t| t.price) * self.quantity
+    }
+
+    /// How much is owed for the stack and whatever is inside it. An unpaid container
+    /// only costs its own price, as what's been put in it may not be the shop's.
+    pub fn bill(&self) -> u32 {
+        (if self.unpaid { self.own_price() } else { 0 }) + self.contents.iter().map(|item| item.bill()).sum::<u32>()
+    }
+
+    /// Makes the stack, and anything in it, a shop's stock. Gold isn't for sale, and
+    /// neither are chests, which stay where they are; only what's in them is.
+    pub fn put_up_for_sale(&mut self) {
+        self.for_sale = self.item_type != ItemType::COIN && self.name != CHEST;
+        self.unpaid = false;
+        self.contents.iter_mut().for_each(|item| item.put_up_for_sale());
+    }
+
+    /// Takes the stack, and anything in it, out of a shop's stock. Whatever was
+    /// the shop's is owed for if `owed`, and free if the shop can't ask for it.
+    pub fn claim(&mut self, owed: bool) {
+        if self.for_sale {
+            self.for_sale = false;
+            self.unpaid = owed;
+        }
+        self.contents.iter_mut().for_each(|item| item.claim(owed));
+    }
+
+    /// Whether other items can be put in the item
+    pub fn is_container(&self) -> bool {
+        find(&self.name).map_or(false, |t| t.container)
+    }
+
+    /// What using the item does, if it can be used
+    pub fn effect(&self) -> Option<Effect> {
+        find(&self.name).and_then(|t| t.effect)
+    }
+
+    /// What using the item is called in menus, if it can be used
+    pub fn use_verb(&self) -> Option<&'static str> {
+        self.effect()?;
+        match self.item_type {
+            ItemType::POTION => Some("Drink"),
+            ItemType::SCROLL => Some("Read"),
+            ItemType::RING => Some(if self.worn { "Take off" } else { "Put on" }),
+            ItemType::WAND => Some("Zap"),
+            _ => None,
+        }
+    }
+
+    /// Whether `other` can be merged into this item's stack
+    pub fn stacks_with(&self, other: &Item) -> bool {
+        self.stackable && other.stackable && self.item_type == other.item_type && self.name == other.name
+            && self.for_sale == other.for_sale && self.unpaid == other.unpaid
+    }
+
+    /// The item by its true name, e.g. "Sword" or "12 gold pieces"
+    pub fn describe(&self) -> String {
+        self.describe_as(&self.name)
+    }
+
+    /// The item as the player knows it, e.g. "Potion of healing" or "2 murky potions"
+    pub fn describe_to(&self, discoveries: &Discoveries) -> String {
+        self.describe_as(&discoveries.name_of(self))
+    }
+
+    fn describe_as(&self, name: &str) -> String {
+        let description = if self.quantity == 1 {
+            name.to_string()
+        } else {
+            format!("{} {}", self.quantity, plural(&uncapitalize(name)))
+        };
+
+        if self.worn {
+            format!("{} (worn)", description)
+        } else if self.for_sale {
+            format!("{} (for sale, {} gold)", description, self.price())
+        } else if self.unpaid {
+            format!("{} (unpaid, {} gold)", description, self.own_price())
+        } else if self.locked {
+            format!("{} (locked)", description)
+        } else {
+            description
+        }
+    }
+
     /// Basic render method. See [render_object](../rendering/trait.RenderingComponent.html#tymethod.render_object)
     pub fn render(&self, rendering_component: &mut Box<dyn RenderingComponent>) {
         rendering_component.render_object(self.position(), symbol_for_type(&self.item_type));
@@ -70,16 +330,74 @@ impl Item {
 pub fn place_items(rooms: &Vec<Rect>, random: &mut IsaacRng) -> ItemsMap {
     let mut items = ItemsMap::new();
     let room = rooms[0];
-    let rand_point = room.rand_point(random);
-    items.insert(rand_point, vec![Item {
-        position: rand_point,
-        item_type: ItemType::WEAPON,
-        name: "Sword".to_string(),
-    }]);
+    for name in &["Sword", "Torch"] {
+        let rand_point = room.rand_point(random);
+        add_to_pile(items.entry(rand_point).or_insert_with(Vec::new), find(name).unwrap().spawn(rand_point));
+    }
+
+    for &(name, (least, most)) in &[("Gold piece", GOLD_PILE), ("Arrow", ARROW_BUNDLE)] {
+        let rand_point = rooms[random.gen_range(0, rooms.len())].rand_point(random);
+        let stack = find(name).unwrap().spawn_stack(rand_point, random.gen_range(least, most + 1));
+        add_to_pile(items.entry(rand_point).or_insert_with(Vec::new), stack);
+    }
+
+    // One potion, scroll, ring or wand somewhere on every level
+    let magic = CATALOGUE.iter().filter(|t| t.effect.is_some()).collect::<Vec<_>>();
+    let rand_point = rooms[random.gen_range(0, rooms.len())].rand_point(random);
+    let template = magic[random.gen_range(0, magic.len())];
+    add_to_pile(items.entry(rand_point).or_insert_with(Vec::new), template.spawn(rand_point));
+
+    // Maybe a chest with a few things in it, and a key somewhere if it's locked
+    if random.gen_bool(CHEST_CHANCE) {
+        let rand_point = rooms[random.gen_range(0, rooms.len())].rand_point(random);
+        let mut chest = find(CHEST).unwrap().spawn(rand_point);
+        for _ in 0..random.gen_range(CHEST_LOOT.0, CHEST_LOOT.1 + 1) {
+            let template = magic[random.gen_range(0, magic.len())];
+            add_to_pile(&mut chest.contents, template.spawn(rand_point));
+        }
+
+        chest.locked = random.gen_bool(LOCKED_CHANCE);
+        if chest.locked {
+            let key_point = rooms[random.gen_range(0, rooms.len())].rand_point(random);
+            add_to_pile(items.entry(key_point).or_insert_with(Vec::new), find(KEY).unwrap().spawn(key_point));
+        }
+        add_to_pile(items.entry(rand_point).or_insert_with(Vec::new), chest);
+    }
+
+    if random.gen_bool(BAG_CHANCE) {
+        let rand_point = rooms[random.gen_range(0, rooms.len())].rand_point(random);
+        add_to_pile(items.entry(rand_point).or_insert_with(Vec::new), find("Bag").unwrap().spawn(rand_point));
+    }
 
     items
 }
 
+/// Adds `item` to a pile or an inventory, merging it into a stack of the same kind if there is one
+pub fn add_to_pile(pile: &mut Vec<Item>, item: Item) {
+    match pile.iter_mut().find(|i| i.stacks_with(&item)) {
+        Some(stack) => stack.quantity += item.quantity,
+        None => pile.push(item),
+    }
+}
+
+/// How a pile of items is drawn: the item's own symbol if there's only one stack
+pub fn pile_symbol(pile: &[Item]) -> char {
+    if pile.len() > 1 {
+        PILE_SYM
+    } else {
+        symbol_for_type(&pile[0].item_type)
+    }
+}
+
+/// The plural of an item name, e.g. "arrows", "potions of healing" or "scrolls labeled NR 9"
+fn plural(name: &str) -> String {
+    let noun_end = [" of ", " labeled ", " called "].iter().filter_map(|s| name.find(s)).min();
+    match noun_end {
+        Some(at) => format!("{}s{}", &name[..at], &name[at..]),
+        None => format!("{}s", name),
+    }
+}
+
 pub fn symbol_for_type(item_type: &ItemType) -> char {
     use self::ItemType::*;
     match item_type {
@@ -0,0 +1,335 @@
+use crate::action::Direction;
+
+use std::fs;
+use std::path::Path;
+
+use tcod::input::{Key, KeyCode};
+
+/// Every key code that can be named in a keymap or replay file
+const KEY_CODES: [KeyCode; 67] = {
+    use tcod::input::KeyCode::*;
+    [
+        NoKey, Escape, Backspace, Tab, Enter, Shift, Control, Alt, Pause, CapsLock,
+        PageUp, PageDown, End, Home, Up, Left, Right, Down, PrintScreen, Insert,
+        Delete, LeftWin, RightWin, Apps,
+        Number0, Number1, Number2, Number3, Number4, Number5, Number6, Number7, Number8, Number9,
+        NumPad0, NumPad1, NumPad2, NumPad3, NumPad4, NumPad5, NumPad6, NumPad7, NumPad8, NumPad9,
+        NumPadAdd, NumPadSubtract, NumPadDivide, NumPadMultiply, NumPadDecimal, NumPadEnter,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        NumLock, ScrollLock, Spacebar, Char, Text,
+    ]
+};
+
+/// The keys every preset starts from: the numpad, the arrows, and the keys for leaving the game
+const BASE_BINDINGS: [(&str, Command); 22] = [
+    ("NumPad8", Command::Move(Direction::N)),
+    ("NumPad2", Command::Move(Direction::S)),
+    ("NumPad4", Command::Move(Direction::W)),
+    ("NumPad6", Command::Move(Direction::E)),
+    ("NumPad7", Command::Move(Direction::NW)),
+    ("NumPad9", Command::Move(Direction::NE)),
+    ("NumPad1", Command::Move(Direction::SW)),
+    ("NumPad3", Command::Move(Direction::SE)),
+    ("Up", Command::Move(Direction::N)),
+    ("Down", Command::Move(Direction::S)),
+    ("Left", Command::Move(Direction::W)),
+    ("Right", Command::Move(Direction::E)),
+    ("NumPad5", Command::Wait),
+    ("Escape", Command::Quit),
+    ("shift+Escape", Command::SaveAndQuit),
+    ("g", Command::PickUp),
+    ("i", Command::Inventory),
+    ("U", Command::Use),
+    ("o", Command::Open),
+    ("K", Command::Kick),
+    ("F12", Command::Wizard),
+    ("~", Command::Console),
+];
+
+const NUMPAD_BINDINGS: [(&str, Command); 2] = [
+    (".", Command::Wait),
+    ("s", Command::Search),
+];
+
+const VI_BINDINGS: [(&str, Command); 10] = [
+    ("k", Command::Move(Direction::N)),
+    ("j", Command::Move(Direction::S)),
+    ("h", Command::Move(Direction::W)),
+    ("l", Command::Move(Direction::E)),
+    ("y", Command::Move(Direction::NW)),
+    ("u", Command::Move(Direction::NE)),
+    ("b", Command::Move(Direction::SW)),
+    ("n", Command::Move(Direction::SE)),
+    (".", Command::Wait),
+    ("s", Command::Search),
+];
+
+const WASD_BINDINGS: [(&str, Command); 10] = [
+    ("w", Command::Move(Direction::N)),
+    ("x", Command::Move(Direction::S)),
+    ("a", Command::Move(Direction::W)),
+    ("d", Command::Move(Direction::E)),
+    ("q", Command::Move(Direction::NW)),
+    ("e", Command::Move(Direction::NE)),
+    ("z", Command::Move(Direction::SW)),
+    ("c", Command::Move(Direction::SE)),
+    ("s", Command::Wait),
+    ("f", Command::Search),
+];
+
+/// The things the player can ask for, whichever key they're bound to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Move(Direction),
+    Wait,
+    PickUp,
+    Inventory,
+    /// Drink, read, put on or zap something from the pack
+    Use,
+    /// Look in a chest or bag, to take things out or put them in
+    Open,
+    Kick,
+    Search,
+    Quit,
+    SaveAndQuit,
+    Wizard,
+    Console,
+}
+
+/// Every command, in the order they're listed to the player
+const COMMANDS: [Command; 19] = [
+    Command::Move(Direction::N), Command::Move(Direction::S), Command::Move(Direction::E), Command::Move(Direction::W),
+    Command::Move(Direction::NE), Command::Move(Direction::NW), Command::Move(Direction::SE), Command::Move(Direction::SW),
+    Command::Wait, Command::PickUp, Command::Inventory, Command::Use, Command::Open, Command::Kick, Command::Search, Command::Quit,
+    Command::SaveAndQuit, Command::Wizard, Command::Console,
+];
+
+impl Command {
+    /// The name used for the command in keymap files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Move(Direction::N) => "move-north",
+            Command::Move(Direction::S) => "move-south",
+            Command::Move(Direction::E) => "move-east",
+            Command::Move(Direction::W) => "move-west",
+            Command::Move(Direction::NE) => "move-northeast",
+            Command::Move(Direction::NW) => "move-northwest",
+            Command::Move(Direction::SE) => "move-southeast",
+            Command::Move(Direction::SW) => "move-southwest",
+            Command::Move(Direction::NoDir) => "move-nowhere",
+            Command::Wait => "wait",
+            Command::PickUp => "pick-up",
+            Command::Inventory => "inventory",
+            Command::Use => "use",
+            Command::Open => "open",
+            Command::Kick => "kick",
+            Command::Search => "search",
+            Command::Quit => "quit",
+            Command::SaveAndQuit => "save-and-quit",
+            Command::Wizard => "wizard",
+            Command::Console => "console",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Command> {
+        COMMANDS.iter().cloned().find(|c| c.name() == name)
+    }
+}
+
+/// The sets of bindings a keymap can start from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preset {
+    /// The numpad and arrow keys
+    Numpad,
+    /// hjklyubn, for laptops and vi users
+    Vi,
+    /// w/a/x/d with q/e/z/c for the diagonals, for the left hand
+    Wasd,
+}
+
+impl Preset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Numpad => "numpad",
+            Preset::Vi => "vi",
+            Preset::Wasd => "wasd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        [Preset::Numpad, Preset::Vi, Preset::Wasd].iter().cloned().find(|p| p.name() == name)
+    }
+
+    fn bindings(&self) -> &'static [(&'static str, Command)] {
+        match self {
+            Preset::Numpad => &NUMPAD_BINDINGS,
+            Preset::Vi => &VI_BINDINGS,
+            Preset::Wasd => &WASD_BINDINGS,
+        }
+    }
+}
+
+/// A key as it's named in a keymap: a key code, or the character typed,
+/// plus whether shift is held.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeySpec {
+    pub code: KeyCode,
+    pub printable: char,
+    pub shift: bool,
+}
+
+impl KeySpec {
+    /// Parses a key name: a single character such as `h` or `.`, or a key
+    /// code such as `NumPad8`, optionally with a `shift+` prefix.
+    pub fn parse(name: &str) -> Option<KeySpec> {
+        let (shift, name) = if name.len() > "shift+".len() && name.starts_with("shift+") {
+            (true, &name["shift+".len()..])
+        } else {
+            (false, name)
+        };
+
+        let mut chars = name.chars();
+        let first = chars.next()?;
+        if chars.next().is_none() {
+            return Some(KeySpec { code: KeyCode::Char, printable: first, shift })
+        }
+
+        let code = key_code_from_name(name)?;
+        Some(KeySpec { code, printable: '\0', shift })
+    }
+
+    /// Whether a keypress is this key. Characters are matched on what was
+    /// typed, since shift already changes that.
+    pub fn matches(&self, key: &Key) -> bool {
+        if self.code == KeyCode::Char {
+            key.code == KeyCode::Char && key.printable == self.printable
+        } else {
+            key.code == self.code && key.shift == self.shift
+        }
+    }
+}
+
+impl std::fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+
+        if self.code == KeyCode::Char {
+            write!(f, "{}", self.printable)
+        } else {
+            write!(f, "{:?}", self.code)
+        }
+    }
+}
+
+/// Translates keypresses into `Command`s.
+///
+/// Keymap files live at `~/.config/mrtom/keys.txt`. Each line is either
+/// `preset <name>`, choosing the bindings to start from, or
+/// `bind <key> <command>`, binding a key on top of them. Lines starting
+/// with `#` are comments.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    pub preset: Preset,
+    bindings: Vec<(KeySpec, Command)>,
+}
+
+impl Keymap {
+    /// A keymap with just the bindings of `preset`
+    pub fn new(preset: Preset) -> Keymap {
+        let mut keymap = Keymap { preset, bindings: vec![] };
+
+        for &(name, command) in BASE_BINDINGS.iter().chain(preset.bindings().iter()) {
+            keymap.bind(KeySpec::parse(name).unwrap(), command);
+        }
+
+        keymap
+    }
+
+    /// Reads a keymap file, returning the keymap and a list of problems found with it.
+    /// Problems don't stop the rest of the file from being used.
+    pub fn load(path: &Path) -> std::io::Result<(Keymap, Vec<String>)> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Builds a keymap from the contents of a keymap file
+    pub fn parse(contents: &str) -> (Keymap, Vec<String>) {
+        let mut problems = vec![];
+        let mut preset = Preset::Numpad;
+        let mut overrides: Vec<(usize, KeySpec, Command)> = vec![];
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words.as_slice() {
+                ["preset", name] => match Preset::from_name(name) {
+                    Some(p) => preset = p,
+                    None => problems.push(format!("line {}: unknown preset '{}'", i + 1, name)),
+                }
+                ["bind", key, command] => match (KeySpec::parse(key), Command::from_name(command)) {
+                    (Some(key), Some(command)) => {
+                        if let Some(&(first, _, old)) = overrides.iter().find(|(_, k, c)| *k == key && *c != command) {
+                            problems.push(format!("line {}: {} is already bound to {} on line {}", i + 1, key, old.name(), first));
+                        }
+                        overrides.push((i + 1, key, command));
+                    }
+                    (None, _) => problems.push(format!("line {}: unknown key '{}'", i + 1, key)),
+                    (_, None) => problems.push(format!("line {}: unknown command '{}'", i + 1, command)),
+                }
+                _ => problems.push(format!("line {}: expected 'preset <name>' or 'bind <key> <command>'", i + 1)),
+            }
+        }
+
+        let mut keymap = Keymap::new(preset);
+        for (_, key, command) in overrides {
+            keymap.bind(key, command);
+        }
+
+        for command in COMMANDS.iter() {
+            if keymap.keys_for(*command).is_empty() {
+                problems.push(format!("nothing is bound to {}", command.name()));
+            }
+        }
+
+        (keymap, problems)
+    }
+
+    /// Binds `key` to `command`, replacing whatever it was bound to before
+    pub fn bind(&mut self, key: KeySpec, command: Command) {
+        self.bindings.retain(|(k, _)| *k != key);
+        self.bindings.push((key, command));
+    }
+
+    /// The command a keypress asks for, if any
+    pub fn command_for(&self, key: &Key) -> Option<Command> {
+        self.bindings.iter().find(|(k, _)| k.matches(key)).map(|&(_, c)| c)
+    }
+
+    /// Every key bound to `command`
+    pub fn keys_for(&self, command: Command) -> Vec<KeySpec> {
+        self.bindings.iter().filter(|(_, c)| *c == command).map(|&(k, _)| k).collect()
+    }
+
+    /// The keymap written out in the keymap file format
+    pub fn to_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("preset {}", self.preset.name())];
+        lines.extend(self.bindings.iter().map(|(k, c)| format!("bind {} {}", k, c.name())));
+        lines
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::new(Preset::Numpad)
+    }
+}
+
+/// Looks up a key code by the name it's given in keymap and replay files, e.g. `NumPad8`
+pub fn key_code_from_name(name: &str) -> Option<KeyCode> {
+    KEY_CODES.iter().cloned().find(|c| format!("{:?}", c) == name)
+}
@@ -1,11 +1,38 @@
-use crate::actor::{Actor, Entity};
+use crate::actor::{Actor, Entity, Alertness, Cause};
 use crate::item::ItemsMap;
-use crate::map::{DungeonMapComponent, MapComponent};
+use crate::identify::Discoveries;
+use crate::map::{MapComponent, Generator};
+use crate::ai::{DijkstraMaps, descend};
+use crate::monster::{self, MonsterTemplate};
+use crate::shop::{self, Shop, SHOPKEEPER_NAME};
+use crate::container::Container;
+use crate::action::Direction;
+
+use crate::util::Point;
+use crate::map::TileKind;
+
+use crate::rng::RngStreams;
+use crate::keymap::Command;
 
-use rand_isaac::IsaacRng;
 use rand::Rng;
 
-use tcod::input::Key;
+/// How many monsters the level tries to spawn
+const MONSTERS_PER_LEVEL: i32 = 5;
+/// The chance of a monster being asleep when the level is created
+const SPAWN_ASLEEP_CHANCE: f64 = 2.0 / 3.0;
+/// How far `free_point_near` looks for a free tile
+const FREE_POINT_SEARCH_RADIUS: i32 = 5;
+/// How many random tiles `random_free_point` tries before giving up
+const RANDOM_FREE_POINT_ATTEMPTS: u32 = 1000;
+
+/// A sound made somewhere on the level that monsters may hear
+#[derive(Clone, Copy, Debug)]
+pub struct Noise {
+    /// Where the sound came from
+    pub origin: Point,
+    /// How far away the sound can be heard
+    pub radius: i32,
+}
 
 /// Struct containing all of the data necessary
 /// for representing a single level of the game.
@@ -16,17 +43,48 @@ pub struct Level {
     pub items: ItemsMap,
     /// The actual `MapComponent` that hold the meat of the level data
     pub map_component: Box<dyn MapComponent + 'static>,
-    /// Input handed down from the Game
-    pub input: Option<Key>,
+    /// The player's command, handed down from the Game
+    pub command: Option<Command>,
+    /// Which stacks of the pile underfoot the player chose to pick up, handed down from the pickup menu
+    pub pickup_choice: Option<Vec<usize>>,
+    /// Which item in the pack the player chose to use, and where to zap it, handed down from the inventory
+    pub use_choice: Option<(usize, Option<Point>)>,
+    /// Which container the player opened, and what to take out of it and put into it from the pack, handed down from the container menu
+    pub open_choice: Option<(Container, Vec<usize>, Vec<usize>)>,
+    /// Which way the player chose to kick, handed down from the direction prompt
+    pub kick_choice: Option<Direction>,
     /// The entity who can act at a given moment
-    pub current_actor: usize
+    pub current_actor: usize,
+    /// Messages generated while entities act, handed up to the Game
+    pub messages: Vec<String>,
+    /// The names of monsters the player has killed, handed up to the Game
+    pub kills: Vec<String>,
+    /// The game's random streams, handed from level to level
+    pub rng: RngStreams,
+    /// What the player knows about unidentified items, handed from level to level
+    pub discoveries: Discoveries,
+    /// The level's shop, if it has one
+    pub shop: Option<Shop>,
+    /// Whether the player has bumped into the shopkeeper, handed up to the PlayState to open the shop
+    pub trading: bool,
+    /// Noises made during the current round
+    pub noises: Vec<Noise>,
+    /// Tiles next to the player that pack monsters have already chosen to attack from this round
+    pub pack_claims: Vec<Point>,
+    /// The navigation maps shared by all monsters on the level
+    pub dijkstra: DijkstraMaps,
+    /// Bumped whenever terrain changes in a way that affects movement costs
+    pub map_revision: u32,
+    /// Whether monsters paint their plans onto the map (wizard mode)
+    pub show_ai: bool,
 }
 
 impl Level {
-    /// Creates a basic level with a default dungeon map and some random items
-    pub fn new(width: i32, height: i32, random: &mut IsaacRng, p: Option<Entity>) -> Level {
-        let mut mc: Box<dyn MapComponent> = box DungeonMapComponent::new_empty(width, height, random);
-        let items = crate::item::place_items(mc.get_rooms(), random);
+    /// Creates a basic level with a map laid out by `generator`, monsters from `templates` and some random items
+    pub fn new(width: i32, height: i32, generator: Generator, templates: &[MonsterTemplate], mut rng: RngStreams, discoveries: Discoveries, p: Option<Entity>) -> Level {
+        let random = &mut rng.mapgen;
+        let mut mc = generator.generate(width, height, random);
+        let mut items = crate::item::place_items(mc.get_rooms(), &mut rng.loot);
         let player_pos = mc.get_player_start();
 
         let mut entities = vec![];
@@ -40,7 +98,7 @@ impl Level {
 
         mc.get_map_mut()[player_pos.x as usize][player_pos.y as usize].occupied = true;
 
-        for _ in 0..3 {
+        for _ in 0..MONSTERS_PER_LEVEL {
             // Get a random room
             let room_num = random.gen_range(0, mc.get_rooms().len());
             let room = mc.get_rooms()[room_num];
@@ -48,16 +106,227 @@ impl Level {
             // Pick random coordinates in that room
             let rand_point = room.rand_point(random);
 
-            // Spawn a monster there
-            entities.push(Some(Entity::kobold(rand_point.x, rand_point.y)));
+            let tile = &mut mc.get_map_mut()[rand_point.x as usize][rand_point.y as usize];
+            if tile.occupied || tile.blocked {
+                continue
+            }
+            tile.occupied = true;
+
+            // Spawn a monster there, most likely asleep
+            let mut monster = monster::random_template(templates, random).spawn(rand_point.x, rand_point.y);
+            monster.alertness = if random.gen_bool(SPAWN_ASLEEP_CHANCE) { Alertness::Asleep } else { Alertness::Wandering };
+            entities.push(Some(monster));
         }
 
+        let shop = shop::generate(&mut mc, &mut items, &mut rng.loot).map(|(shop, keeper)| {
+            entities.push(Some(keeper));
+            shop
+        });
+
         Level {
             items,
             entities,
             map_component: mc,
-            input: None,
-            current_actor: 0
+            command: None,
+            pickup_choice: None,
+            use_choice: None,
+            open_choice: None,
+            kick_choice: None,
+            current_actor: 0,
+            messages: vec![],
+            kills: vec![],
+            rng,
+            discoveries,
+            shop,
+            trading: false,
+            noises: vec![],
+            pack_claims: vec![],
+            dijkstra: DijkstraMaps::new(),
+            map_revision: 0,
+            show_ai: false,
+        }
+    }
+
+    /// Reveals the hidden tile at `point`, such as a trap
+    pub fn reveal(&mut self, point: Point) {
+        self.map_component.get_map_mut()[point.x as usize][point.y as usize].hidden = false;
+        self.map_revision += 1;
+    }
+
+    /// The next step from `from` toward the nearest of `targets`, along the shared Dijkstra map
+    pub fn approach_step(&mut self, from: Point, targets: &[Point]) -> Option<Point> {
+        let values = self.dijkstra.approach(&self.map_component, self.map_revision, targets);
+        descend(&self.map_component, values, from)
+    }
+
+    /// The next step from `from` away from `threats`, along the shared safety map
+    pub fn flee_step(&mut self, from: Point, threats: &[Point]) -> Option<Point> {
+        let values = self.dijkstra.flee(&self.map_component, self.map_revision, threats);
+        descend(&self.map_component, values, from)
+    }
+
+    /// The next step from `from` toward the nearest item on the floor
+    pub fn item_step(&mut self, from: Point) -> Option<Point> {
+        let mut positions = self.items.keys().cloned().collect::<Vec<_>>();
+        positions.sort();
+
+        let values = self.dijkstra.items(&self.map_component, self.map_revision, &positions);
+        descend(&self.map_component, values, from)
+    }
+
+    /// Finds the index of the entity standing at `point`, if there is one
+    pub fn entity_at(&self, point: Point) -> Option<usize> {
+        self.entities.iter().position(|e| e.as_ref().map_or(false, |e| e.get_position() == point))
+    }
+
+    /// Makes a noise that monsters within `radius` of `origin` may hear
+    pub fn make_noise(&mut self, origin: Point, radius: i32) {
+        self.noises.push(Noise { origin, radius });
+    }
+
+    /// Rolls whether a monster at `position` notices any of this round's noises, returning where the noise came from.
+    /// Louder and closer noises are more likely to be heard, and sleeping monsters hear less.
+    pub fn heard_noise(&mut self, position: Point, alertness: Alertness) -> Option<Point> {
+        for i in 0..self.noises.len() {
+            let noise = self.noises[i];
+            let distance = position.distance(&noise.origin) as i32;
+            if distance > noise.radius {
+                continue
+            }
+
+            let mut chance = (noise.radius - distance + 1) as f64 / (noise.radius + 1) as f64;
+            if alertness == Alertness::Asleep {
+                chance /= 2.0;
+            }
+
+            if self.rng.ai.gen_bool(chance) {
+                return Some(noise.origin)
+            }
+        }
+
+        None
+    }
+
+    /// Wakes every monster on the level and sets them hunting
+    pub fn wake_all(&mut self) {
+        for entity in self.entities.iter_mut().skip(1) {
+            entity.as_mut().unwrap().alertness = Alertness::Hunting;
+        }
+    }
+
+    /// Picks a random plain floor tile that nobody is standing on, if one turns up
+    /// within `RANDOM_FREE_POINT_ATTEMPTS` tries
+    pub fn random_free_point(&mut self) -> Option<Point> {
+        for _ in 0..RANDOM_FREE_POINT_ATTEMPTS {
+            let rooms = self.map_component.get_rooms();
+            let room = rooms[self.rng.combat.gen_range(0, rooms.len())];
+            let point = room.rand_point(&mut self.rng.combat);
+
+            let tile = self.map_component.get_map()[point.x as usize][point.y as usize];
+            if tile.kind == TileKind::Floor && !tile.occupied {
+                return Some(point)
+            }
+        }
+
+        None
+    }
+
+    /// Finds the closest walkable tile to `point` that nobody is standing on, if there's one nearby
+    pub fn free_point_near(&self, point: Point) -> Option<Point> {
+        for radius in 1..FREE_POINT_SEARCH_RADIUS + 1 {
+            for x in (point.x - radius)..(point.x + radius + 1) {
+                for y in (point.y - radius)..(point.y + radius + 1) {
+                    let map = &self.map_component;
+                    if map.contains(x, y) && !map.is_blocked(x, y) && !map.is_occupied(x, y) {
+                        return Some(Point { x, y })
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Moves an entity straight to `to`, keeping track of which tiles are occupied
+    pub fn teleport(&mut self, index: usize, to: Point) {
+        let from = self.entities[index].as_ref().unwrap().get_position();
+        self.entities[index].as_mut().unwrap().set_position(to);
+
+        let map = self.map_component.get_map_mut();
+        map[from.x as usize][from.y as usize].occupied = false;
+        map[to.x as usize][to.y as usize].occupied = true;
+    }
+
+    /// Adds a new entity to the level where it stands
+    pub fn spawn(&mut self, entity: Entity) {
+        let position = entity.get_position();
+        self.map_component.get_map_mut()[position.x as usize][position.y as usize].occupied = true;
+        self.entities.push(Some(entity));
+    }
+
+    /// The index of the shopkeeper minding the level's shop, if they're still around
+    pub fn shopkeeper(&self) -> Option<usize> {
+        self.shop.as_ref()?;
+        self.entities.iter().position(|e| e.as_ref().map_or(false, |e| e.name == SHOPKEEPER_NAME))
+    }
+
+    /// Whether the level has a shop that's still minding its stock: it hasn't been robbed and its shopkeeper is around
+    pub fn shop_open(&self) -> bool {
+        self.shop.as_ref().map_or(false, |shop| !shop.robbed) && self.shopkeeper().is_some()
+    }
+
+    /// Whether `point` is on the floor of a shop that's still minding its stock
+    pub fn in_shop(&self, point: Point) -> bool {
+        self.shop_open() && self.shop.as_ref().unwrap().contains(point)
+    }
+
+    /// Turns the shopkeeper on the player if they've left the shop without paying.
+    /// Once the shop has been robbed, or its shopkeeper is gone, nothing more is owed.
+    pub fn check_shop(&mut self) {
+        let position = self.entities[0].as_ref().unwrap().get_position();
+        let owing = self.entities[0].as_ref().unwrap().bill() > 0;
+        if !owing || self.in_shop(position) {
+            return
+        }
+
+        self.entities[0].as_mut().unwrap().clear_unpaid();
+        let keeper = match (self.shopkeeper(), self.shop.as_mut()) {
+            (Some(keeper), Some(shop)) if !shop.robbed => {
+                shop.robbed = true;
+                keeper
+            }
+            _ => return,
+        };
+
+        let keeper = self.entities[keeper].as_mut().unwrap();
+        keeper.is_hostile = true;
+        keeper.alertness = Alertness::Hunting;
+        self.log("You leave without paying! The shopkeeper is furious".to_string());
+    }
+
+    /// Queues a message to be shown to the player
+    pub fn log(&mut self, message: String) {
+        self.messages.push(message);
+    }
+
+    /// Removes every monster that has run out of health, freeing the tiles they stood on.
+    /// The player is never removed, as their death ends the game instead.
+    pub fn remove_dead(&mut self) {
+        let mut i = 1;
+        while i < self.entities.len() {
+            if self.entities[i].as_ref().unwrap().is_dead() {
+                let dead = self.entities.remove(i).unwrap();
+                let pos = dead.get_position();
+                self.map_component.get_map_mut()[pos.x as usize][pos.y as usize].occupied = false;
+                self.log(format!("The {} dies", dead.name));
+                if dead.last_hurt_by == Some(Cause::Player) {
+                    // Tougher monsters are worth more
+                    self.entities[0].as_mut().unwrap().xp += dead.max_health as u32;
+                    self.kills.push(dead.name);
+                }
+            } else {
+                i += 1;
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,38 @@
+#![feature(box_syntax)]
+pub mod util;
+pub mod config;
+pub mod rng;
+pub mod game;
+pub mod rendering;
+pub mod brain;
+pub mod actor;
+pub mod map;
+pub mod ai;
+pub mod behaviour;
+pub mod monster;
+pub mod level;
+pub mod item;
+pub mod identify;
+pub mod shop;
+pub mod container;
+pub mod action;
+pub mod state;
+pub mod ui;
+pub mod replay;
+pub mod cli;
+pub mod keymap;
+pub mod wizard;
+pub mod console;
+pub mod morgue;
+pub mod scores;
+pub mod save;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum Exit {
+    Save,
+    Die,
+    /// Leave from the main menu, before a game has started
+    Quit,
+    /// The window was closed in the middle of a game, which leaves its autosave to be resumed from
+    Close,
+}
@@ -1,110 +1,184 @@
 #![feature(box_syntax)]
-pub mod util;
-pub mod game;
-pub mod rendering;
-pub mod brain;
-pub mod actor;
-pub mod map;
-pub mod ai;
-pub mod level;
-pub mod item;
-pub mod action;
-pub mod state;
-pub mod ui;
-
-use game::Game;
+use roguelike::{cli, save, morgue, monster, Exit};
+use roguelike::game::Game;
 
 use std::fs;
+use std::env;
 use std::path::PathBuf;
+use std::time::Duration;
+use std::process;
 use std::io::{ErrorKind, Error as IOE};
 
-use dirs::home_dir;
-use crate::state::{PlayState, State};
-
-#[derive(PartialEq, Copy, Clone)]
-pub enum Exit {
-    Save,
-    Die
-}
+use roguelike::config::{config_path, now};
+use roguelike::state::{self, PlayState, SplashState, State};
+use roguelike::replay::Replay;
+use roguelike::keymap::Keymap;
+use roguelike::save::{Slot, SaveData};
+use roguelike::actor::Actor;
 
 fn main() {
-    let seed = try_load_game();
+    let options = match cli::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
 
     let mut states: Vec<Box<dyn State>> = vec![];
+    // Asking for a particular game on the command line skips the main menu
+    let skip_menu = options.replay.is_some() || options.seed.is_some() || options.new_game;
+
+    // Read once, so every level of the game is filled from the same catalogue
+    let monsters = monster::catalogue();
+
+    if let Some(file) = &options.replay {
+        let game = Replay::load(file).map_err(|e| e.to_string())
+            .and_then(|replay| Game::from_replay(replay, monsters, Duration::from_millis(options.delay), options.headless));
+        match game {
+            Ok(game) => states.push(box PlayState::new(game)),
+            Err(e) => {
+                eprintln!("Could not load replay {}: {}", file.display(), e);
+                process::exit(1);
+            }
+        }
+    } else {
+        let (keymap, problems) = load_keymap();
+        let mut game = Game::new(options.seed, options.settings, keymap, monsters);
+        for problem in problems {
+            eprintln!("keys.txt: {}", problem);
+            game.game_log(format!("keys.txt: {}", problem));
+        }
 
-    let game = Game::new(seed);
-    let play_state = Box::new(PlayState::new(game));
+        if skip_menu {
+            game.save_slot = match &options.save_file {
+                Some(path) => Some(Slot::at(path)),
+                None => Slot::named(&save::unused_name()),
+            };
+            states.push(box PlayState::new(game));
+        } else {
+            let (slots, interrupted) = match &options.save_file {
+                Some(path) => {
+                    let slot = Slot::at(path);
+                    (Ok(if slot.exists() { vec![slot.clone()] } else { vec![] }),
+                     if slot.autosave_path().exists() { vec![slot] } else { vec![] })
+                }
+                None => (save::slots(), save::interrupted()),
+            };
+            states.push(box SplashState::new(game, slots, interrupted, options.save_file.clone()));
+        }
+    }
 
-    states.push(play_state);
+    let save = state::run(&mut states, !skip_menu);
 
-    states[0].render();
-    let save = loop {
-        let mut i = states.len() - 1;
+    let game = states.last_mut().unwrap().get_game_mut();
 
-        if states[i].get_game().rendering_component.get_root_console().window_closed() {
-            break Exit::Die
+    // Replays leave the real save alone
+    if options.replay.is_some() {
+        if options.headless {
+            print_replay_summary(game);
         }
+        return;
+    }
 
-        // Update state
-        if states[i].should_exit() {
-            let game = states.pop().unwrap().exit();
-            i -= 1;
-            states[i].set_game(game);
-        } else {
-            if let Some(new_state) = states[i].maybe_new_state() {
-                states.push(new_state);
-                i += 1;
-            }
-        }
+    if save == Exit::Quit {
+        return;
+    }
 
-        states[i].update();
-        states[i].render();
+    // A window closed on the game over screen is a game over all the same
+    let save = if save == Exit::Close && game.level.entities[0].as_ref().unwrap().is_dead() { Exit::Die } else { save };
+
+    if let Err(e) = game.finish_autosave() {
+        eprintln!("Could not autosave: {}", e.to_string());
+    }
 
-        // Check if the game should exit
-        if let Some(exit) = states[i].maybe_exit_game() {
-            break exit
+    if let Err(e) = record_replay(&game.recording) {
+        eprintln!("Could not record replay: {}", e.to_string());
+    }
+
+    // The game wasn't finished, so its save and autosave are kept for resuming it
+    if save == Exit::Close {
+        return;
+    }
+
+    if save == Exit::Die {
+        match write_morgue(game) {
+            Ok(path) => println!("Morgue file written to {}", path.display()),
+            Err(e) => eprintln!("Could not write morgue file: {}", e.to_string()),
+        }
+    }
+
+    let slot = match &game.save_slot {
+        Some(slot) => slot,
+        None => {
+            eprintln!("Could not find a config directory to save in");
+            return;
         }
     };
 
     // If the player wants to save, attempt to write game info
     if save == Exit::Save {
-        match save_game(states.last().unwrap().get_game().seed) {
-            Err(e) => eprintln!("Could not save: {}", e.to_string()),
-            _ => ()
+        let mut data = SaveData::new(game);
+        // The key that saved the game would only save it again when it's loaded
+        if let Some(replay) = data.replay.as_mut() {
+            replay.keys.pop();
+        }
+
+        if let Err(e) = slot.write(&data) {
+            eprintln!("Could not save to {}: {}", slot.path.display(), e.to_string());
         }
         return;
     }
 
-    // Else, scrub the save file to prevent game replay
-    match fs::File::create([home_dir().unwrap().to_str().unwrap(), ".config", "mrtom", "save.dat"].iter().collect::<PathBuf>()) {
-        _ => () // To prevent warnings (they're annoying)
+    // Else, delete the save and any autosave to prevent game replay
+    if let Err(e) = slot.delete() {
+        eprintln!("Could not delete the save {}: {}", slot.path.display(), e.to_string());
     }
 }
 
-// Attempts to load a savegame
-fn try_load_game() -> Option<u64> {
-    let user_home = home_dir()?;
-    let seed = fs::read_to_string([user_home.to_str().unwrap(), ".config", "mrtom", "save.dat"].iter().collect::<PathBuf>());
-
-    match seed {
-        Ok(s) => {
-            let s = s.parse::<u64>();
-            if let Ok(t) = s {
-                Some(t)
-            } else {
-                return None;
-            }
-        }
-        Err(_) => None
+// Loads the player's keymap, if they've made one, along with any problems with it
+fn load_keymap() -> (Keymap, Vec<String>) {
+    let keymap_file = match config_path("keys.txt") {
+        Some(f) => f,
+        None => return (Keymap::default(), vec![]),
+    };
+
+    match Keymap::load(&keymap_file) {
+        Ok(loaded) => loaded,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => (Keymap::default(), vec![]),
+        Err(e) => (Keymap::default(), vec![format!("could not be read: {}", e.to_string())]),
     }
 }
 
-fn save_game(seed: u64) -> std::io::Result<()> {
-    let user_home = home_dir().ok_or(IOE::from(ErrorKind::NotFound))?;
-    let mut game_dir: PathBuf = [user_home.to_str().unwrap(), ".config", "mrtom"].iter().collect();
-    fs::create_dir_all(&game_dir)?;
+// Writes the game's keypresses to the config directory so it can be replayed
+fn record_replay(replay: &Replay) -> std::io::Result<()> {
+    let replay_file = config_path("last_game.replay").ok_or(IOE::from(ErrorKind::NotFound))?;
+    fs::create_dir_all(replay_file.parent().unwrap())?;
+
+    replay.save(&replay_file)
+}
+
+// Writes the morgue file for a finished game to the config directory, returning where it went
+fn write_morgue(game: &Game) -> std::io::Result<PathBuf> {
+    let morgue_file = config_path(&format!("morgue-{}-{}.txt", game.seed, now())).ok_or(IOE::from(ErrorKind::NotFound))?;
+
+    morgue::write(game, &morgue_file)?;
+    Ok(morgue_file)
+}
 
-    game_dir.push("save.dat");
-    fs::write(game_dir, seed.to_string())?;
-    Ok(())
+// Prints where a headless replay left the player, for comparing runs
+fn print_replay_summary(game: &Game) {
+    let player = game.level.entities[0].as_ref().unwrap();
+    let position = player.get_position();
+
+    println!("seed {}: replay finished", game.seed);
+    println!("player at ({}, {}) with {}/{} health", position.x, position.y, player.health, player.max_health);
+    for message in game.message_cache.iter().chain(game.message_queue.iter()) {
+        println!("{}", message);
+    }
 }
@@ -14,13 +14,143 @@ const ROOM_MAX_SIZE: i32 = 10;
 const ROOM_MIN_SIZE: i32 = 6;
 /// Maximum number of rooms in a level.
 const MAX_ROOMS: i32 = 30;
+/// Maximum number of terrain patches placed in a single room.
+const TERRAIN_PATCHES_PER_ROOM: i32 = 2;
+/// Maximum radius of a terrain patch.
+const TERRAIN_PATCH_MAX_RADIUS: i32 = 2;
+/// Number of traps hidden in a level.
+const TRAPS_PER_LEVEL: i32 = 6;
+
+/// The ways a level's map can be laid out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Generator {
+    /// Rooms joined up by tunnels
+    Dungeon,
+    /// A single open room filling the whole map
+    Arena,
+}
+
+impl Generator {
+    /// Every generator, in the order they're listed to the player
+    pub const ALL: [Generator; 2] = [Generator::Dungeon, Generator::Arena];
+
+    /// The name used for the generator on the command line and in replays
+    pub fn name(&self) -> &'static str {
+        match self {
+            Generator::Dungeon => "dungeon",
+            Generator::Arena => "arena",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Generator> {
+        Self::ALL.iter().cloned().find(|g| g.name() == name)
+    }
+
+    /// Lays out a new map of the given size
+    pub fn generate(&self, width: i32, height: i32, random: &mut IsaacRng) -> Box<dyn MapComponent> {
+        match self {
+            Generator::Dungeon => box DungeonMapComponent::new(width, height, random),
+            Generator::Arena => box DungeonMapComponent::new_empty(width, height, random),
+        }
+    }
+}
 
 /// Type alias for a `Vec` of `Vec` of `Tile`s.
 pub type Map = Vec<Vec<Tile>>;
 
+/// The kinds of terrain a `Tile` can be made of.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TileKind {
+    Floor,
+    Wall,
+    Water,
+    DeepWater,
+    Lava,
+    Rubble,
+    Grass,
+    Trap(TrapKind),
+}
+
+/// The kinds of trap that can be hidden in the floor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrapKind {
+    Dart,
+    Pit,
+    Teleport,
+    Alarm,
+}
+
+impl TrapKind {
+    /// The name used to refer to this kind of trap in messages, article included.
+    pub fn name(&self) -> &'static str {
+        use self::TrapKind::*;
+        match self {
+            Dart => "a dart trap",
+            Pit => "a pit",
+            Teleport => "a teleportation trap",
+            Alarm => "an alarm trap",
+        }
+    }
+}
+
+impl TileKind {
+    /// The character used to draw this kind of terrain.
+    pub fn glyph(&self) -> char {
+        use self::TileKind::*;
+        match self {
+            Floor => '.',
+            Wall => '+',
+            Water | DeepWater | Lava => '~',
+            Rubble => ':',
+            Grass => '"',
+            Trap(_) => '^',
+        }
+    }
+
+    /// The color used to draw this kind of terrain while it is in view.
+    pub fn color(&self) -> Color {
+        use self::TileKind::*;
+        match self {
+            Floor | Wall => Color { r: 255, g: 255, b: 255 },
+            Water => Color { r: 64, g: 128, b: 255 },
+            DeepWater => Color { r: 0, g: 0, b: 191 },
+            Lava => Color { r: 255, g: 96, b: 0 },
+            Rubble => Color { r: 159, g: 127, b: 95 },
+            Grass => Color { r: 0, g: 191, b: 0 },
+            Trap(_) => Color { r: 255, g: 0, b: 255 },
+        }
+    }
+
+    /// Whether this kind of terrain blocks walking paths.
+    pub fn blocks_movement(&self) -> bool {
+        *self == TileKind::Wall
+    }
+
+    /// Whether this kind of terrain blocks the sight of `Entity`s.
+    pub fn blocks_sight(&self) -> bool {
+        *self == TileKind::Wall
+    }
+
+    /// The cost of stepping orthogonally onto this terrain, where floor costs 10.
+    pub fn move_cost(&self) -> u32 {
+        use self::TileKind::*;
+        match self {
+            Floor | Grass | Wall => 10,
+            Water | Rubble => 20,
+            DeepWater => 40,
+            Trap(_) => 100,
+            Lava => 200,
+        }
+    }
+}
+
 /// Struct representing one coordinate on the map.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Tile {
+    /// The kind of terrain the tile is made of.
+    pub kind: TileKind,
+    /// Whether the tile's terrain is unknown to the player, e.g. an undiscovered trap.
+    pub hidden: bool,
     /// Whether the tile has an entity on it.
     pub occupied: bool,
     /// Whether the tile blocks walking paths.
@@ -34,14 +164,42 @@ pub struct Tile {
 }
 
 impl Tile {
+    /// Creates a new tile of the given kind.
+    pub fn new(kind: TileKind) -> Self {
+        Tile {
+            kind,
+            hidden: false,
+            occupied: false,
+            blocked: kind.blocks_movement(),
+            block_sight: kind.blocks_sight(),
+            explored: false,
+            color_override: None
+        }
+    }
+
     /// Creates a new floor tile.
     pub fn floor() -> Self {
-        Tile { occupied: false, blocked: false, block_sight: false, explored: false, color_override: None }
+        Tile::new(TileKind::Floor)
     }
 
     /// Creates a new wall tile.
     pub fn wall() -> Self {
-        Tile { occupied: false, blocked: true, block_sight: true, explored: false, color_override: None }
+        Tile::new(TileKind::Wall)
+    }
+
+    /// Creates a new trap tile that has yet to be discovered.
+    pub fn hidden_trap(trap: TrapKind) -> Self {
+        Tile { hidden: true, ..Tile::new(TileKind::Trap(trap)) }
+    }
+
+    /// The kind of terrain the player believes the tile is made of.
+    /// Hidden tiles pass for plain floor.
+    pub fn apparent_kind(&self) -> TileKind {
+        if self.hidden {
+            TileKind::Floor
+        } else {
+            self.kind
+        }
     }
 }
 
@@ -100,10 +258,14 @@ pub trait MapComponent {
     fn render(&mut self, rendering_component: &mut Box<dyn RenderingComponent>, player: &Entity);
     /// Whether the supplied position has the `blocked` flag set.
     fn is_blocked(&self, x: i32, y: i32) -> bool;
+    /// Whether the supplied position has the `block_sight` flag set.
+    fn blocks_sight(&self, x: i32, y: i32) -> bool;
     /// Checks if a position is occupied by and entity
     fn is_occupied(&self, x: i32, y: i32) -> bool;
     /// Gets the bounds (size) of the map
     fn get_bounds(&self) -> Bound;
+    /// The cost of stepping onto the supplied position. See [move_cost](enum.TileKind.html#method.move_cost)
+    fn move_cost(&self, x: i32, y: i32) -> u32;
 }
 
 /// Basic struct for simple dungeon levels.
@@ -148,6 +310,10 @@ impl MapComponent for DungeonMapComponent {
         self.contains(x, y) && self.map[x as usize][y as usize].blocked
     }
 
+    fn blocks_sight(&self, x: i32, y: i32) -> bool {
+        !self.contains(x, y) || self.map[x as usize][y as usize].block_sight
+    }
+
     fn is_occupied(&self, x: i32, y: i32) -> bool {
         self.contains(x, y) && self.map[x as usize][y as usize].occupied
     }
@@ -155,6 +321,15 @@ impl MapComponent for DungeonMapComponent {
     fn get_bounds(&self) -> Bound {
         self.bounds
     }
+
+    fn move_cost(&self, x: i32, y: i32) -> u32 {
+        if self.contains(x, y) {
+            // Monsters only steer around the terrain they know about
+            self.map[x as usize][y as usize].apparent_kind().move_cost()
+        } else {
+            TileKind::Wall.move_cost()
+        }
+    }
 }
 
 impl DungeonMapComponent {
@@ -209,6 +384,9 @@ impl DungeonMapComponent {
             }
         }
 
+        Self::place_terrain(&rooms, player_start, &mut map, random);
+        Self::place_traps(&rooms, player_start, &mut map, random);
+
         DungeonMapComponent {
             rooms,
             map,
@@ -217,7 +395,7 @@ impl DungeonMapComponent {
         }
     }
 
-    pub fn new_empty(width: i32, height: i32, _random: &mut IsaacRng) -> DungeonMapComponent {
+    pub fn new_empty(width: i32, height: i32, random: &mut IsaacRng) -> DungeonMapComponent {
         let mut map = vec![vec![Tile::wall(); height as usize]; width as usize];
         let mut rooms = vec![];
 
@@ -228,6 +406,9 @@ impl DungeonMapComponent {
         rooms.push(rect);
 
         let player_start = Point { x: (width-2)/2, y: (height-2)/2 };
+        Self::place_terrain(&rooms, player_start, &mut map, random);
+        Self::place_traps(&rooms, player_start, &mut map, random);
+
         let bounds = Bound { min: zero_point, max: Point {x: width, y: height} };
 
         DungeonMapComponent {
@@ -238,6 +419,65 @@ impl DungeonMapComponent {
         }
     }
 
+    /// Scatters patches of non-floor terrain over the floor of the given rooms,
+    /// leaving the player's starting tile alone.
+    fn place_terrain(rooms: &Vec<Rect>, player_start: Point, map: &mut Map, random: &mut IsaacRng) {
+        for room in rooms {
+            for _ in 0..random.gen_range(0, TERRAIN_PATCHES_PER_ROOM + 1) {
+                let center = room.rand_point(random);
+                let radius = random.gen_range(1, TERRAIN_PATCH_MAX_RADIUS + 1);
+
+                // Pools get a deeper middle, everything else is a flat patch
+                let (outer, inner) = match random.gen_range(0, 4) {
+                    0 => (TileKind::Water, TileKind::DeepWater),
+                    1 => (TileKind::Lava, TileKind::Lava),
+                    2 => (TileKind::Grass, TileKind::Grass),
+                    _ => (TileKind::Rubble, TileKind::Rubble),
+                };
+
+                for x in (center.x - radius)..(center.x + radius + 1) {
+                    for y in (center.y - radius)..(center.y + radius + 1) {
+                        let point = Point { x, y };
+                        if point == player_start || x <= room.x1 || x >= room.x2 || y <= room.y1 || y >= room.y2 {
+                            continue
+                        }
+
+                        let dist = point.distance(&center) as i32;
+                        if dist > radius {
+                            continue
+                        }
+
+                        let tile = &mut map[x as usize][y as usize];
+                        if tile.kind == TileKind::Floor {
+                            *tile = Tile::new(if dist < radius { inner } else { outer });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Hides traps on random floor tiles of the given rooms, away from the player's start.
+    fn place_traps(rooms: &Vec<Rect>, player_start: Point, map: &mut Map, random: &mut IsaacRng) {
+        for _ in 0..TRAPS_PER_LEVEL {
+            let room = rooms[random.gen_range(0, rooms.len())];
+            let point = room.rand_point(random);
+
+            let tile = &mut map[point.x as usize][point.y as usize];
+            if point == player_start || tile.kind != TileKind::Floor {
+                continue
+            }
+
+            let trap = match random.gen_range(0, 4) {
+                0 => TrapKind::Dart,
+                1 => TrapKind::Pit,
+                2 => TrapKind::Teleport,
+                _ => TrapKind::Alarm,
+            };
+            *tile = Tile::hidden_trap(trap);
+        }
+    }
+
     fn create_room(room: Rect, map: &mut Map) {
         for x in (room.x1 + 1)..room.x2 {
             for y in (room.y1 + 1)..room.y2 {
@@ -0,0 +1,197 @@
+use crate::actor::Entity;
+use crate::util::hash;
+use crate::behaviour::{Node, BehaviourBrainComponent};
+use crate::brain::{BrainComponent, AggroBrainComponent, PackBrainComponent, CowardBrainComponent, ArcherBrainComponent, RandomBrainComponent};
+
+use std::fs;
+
+use rand::Rng;
+use rand_isaac::IsaacRng;
+
+/// Where designers can put their own monster catalogue
+pub const MONSTER_FILE: &str = "data/monsters.txt";
+/// The catalogue built into the game, used when there is no `MONSTER_FILE`
+const DEFAULT_MONSTERS: &str = include_str!("../data/monsters.txt");
+
+/// How a monster decides what to do
+#[derive(Clone, Debug)]
+pub enum BrainSpec {
+    Aggro,
+    Pack,
+    Coward,
+    Wanderer,
+    Archer { preferred_distance: u32, damage: i32 },
+    Behaviour(Node),
+}
+
+/// Everything needed to spawn a kind of monster, as read from the monster catalogue
+#[derive(Clone, Debug)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub health: i32,
+    pub damage: i32,
+    /// How likely the monster is to be spawned, relative to the others
+    pub frequency: u32,
+    pub brain: BrainSpec,
+}
+
+impl MonsterTemplate {
+    /// Creates a new monster of this kind at the given position
+    pub fn spawn(&self, x: i32, y: i32) -> Entity {
+        Entity::new(&self.name, x, y, self.health, self.damage, self.glyph, self.brain.build(), true)
+    }
+}
+
+impl BrainSpec {
+    fn build(&self) -> Box<dyn BrainComponent> {
+        match self {
+            BrainSpec::Aggro => box AggroBrainComponent::new(),
+            BrainSpec::Pack => box PackBrainComponent::new(),
+            BrainSpec::Coward => box CowardBrainComponent::new(),
+            BrainSpec::Wanderer => box RandomBrainComponent::anywhere(),
+            BrainSpec::Archer { preferred_distance, damage } => box ArcherBrainComponent::new(*preferred_distance, *damage),
+            BrainSpec::Behaviour(root) => box BehaviourBrainComponent::new(root.clone()),
+        }
+    }
+
+    fn parse(value: &str) -> Result<BrainSpec, String> {
+        let words = value.split_whitespace().collect::<Vec<_>>();
+        let number = |i: usize| words.get(i).and_then(|w| w.parse::<i32>().ok())
+            .ok_or(format!("brain '{}' is missing a number", value));
+        let distance = |i: usize| words.get(i).and_then(|w| w.parse::<u32>().ok()).filter(|&d| d > 0)
+            .ok_or(format!("brain '{}' is missing a distance above 0", value));
+
+        match words.get(0).cloned() {
+            Some("aggro") => Ok(BrainSpec::Aggro),
+            Some("pack") => Ok(BrainSpec::Pack),
+            Some("coward") => Ok(BrainSpec::Coward),
+            Some("wanderer") => Ok(BrainSpec::Wanderer),
+            Some("archer") => Ok(BrainSpec::Archer { preferred_distance: distance(1)?, damage: number(2)? }),
+            _ => Err(format!("unknown brain '{}'", value)),
+        }
+    }
+}
+
+/// The monsters a game is played with, loaded once when the game starts
+#[derive(Clone, Debug)]
+pub struct Catalogue {
+    pub templates: Vec<MonsterTemplate>,
+    /// A hash of the catalogue's text, kept in replays so a game is only played back with the monsters it was played with
+    pub hash: u64,
+}
+
+impl Catalogue {
+    /// Finds the monster with the given name
+    pub fn find(&self, name: &str) -> Option<&MonsterTemplate> {
+        self.templates.iter().find(|t| t.name == name)
+    }
+
+    /// The name of every monster, in catalogue order
+    pub fn names(&self) -> Vec<String> {
+        self.templates.iter().map(|t| t.name.clone()).collect()
+    }
+}
+
+/// Loads the monster catalogue, preferring `MONSTER_FILE` in the directory the game
+/// is started in over the built-in one. A broken `MONSTER_FILE` is reported and ignored.
+pub fn catalogue() -> Catalogue {
+    if let Ok(source) = fs::read_to_string(MONSTER_FILE) {
+        match parse_catalogue(&source) {
+            Ok(templates) => return Catalogue { templates, hash: hash(source.as_bytes()) },
+            Err(e) => eprintln!("Could not load {}: {}", MONSTER_FILE, e),
+        }
+    }
+
+    let templates = parse_catalogue(DEFAULT_MONSTERS).expect("built-in monster catalogue is broken");
+    Catalogue { templates, hash: hash(DEFAULT_MONSTERS.as_bytes()) }
+}
+
+/// Picks a random monster from the catalogue, weighted by frequency
+pub fn random_template<'a>(templates: &'a [MonsterTemplate], random: &mut IsaacRng) -> &'a MonsterTemplate {
+    let total: u32 = templates.iter().map(|t| t.frequency).sum();
+    let mut roll = random.gen_range(0, total);
+
+    for template in templates {
+        if roll < template.frequency {
+            return template
+        }
+        roll -= template.frequency;
+    }
+
+    &templates[templates.len() - 1]
+}
+
+/// Parses a monster catalogue. See `data/monsters.txt` for the format.
+pub fn parse_catalogue(source: &str) -> Result<Vec<MonsterTemplate>, String> {
+    let mut sections: Vec<(String, Vec<(String, String)>)> = vec![];
+    let mut lines = source.lines().enumerate();
+
+    while let Some((num, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            sections.push((line[1..line.len() - 1].trim().to_string(), vec![]));
+            continue
+        }
+
+        let eq = line.find('=').ok_or(format!("line {}: expected 'key = value'", num + 1))?;
+        let key = line[..eq].trim().to_string();
+        let mut value = line[eq + 1..].trim().to_string();
+
+        // Behaviour trees carry on over following lines until their brackets balance
+        while value.matches('(').count() > value.matches(')').count() {
+            match lines.next() {
+                Some((_, more)) => {
+                    value.push(' ');
+                    value.push_str(more.trim());
+                }
+                None => return Err(format!("line {}: unbalanced brackets", num + 1)),
+            }
+        }
+
+        match sections.last_mut() {
+            Some(section) => section.1.push((key, value)),
+            None => return Err(format!("line {}: '{}' is not inside a [monster] section", num + 1, key)),
+        }
+    }
+
+    let mut templates = vec![];
+    for (name, fields) in sections {
+        let get = |key: &str| fields.iter().find(|f| f.0 == key).map(|f| f.1.clone())
+            .ok_or(format!("{} has no {}", name, key));
+        let number = |key: &str| get(key)?.parse::<i32>().map_err(|_| format!("{}'s {} is not a number", name, key));
+        let frequency = get("frequency")?.parse::<u32>().ok().filter(|&f| f > 0)
+            .ok_or(format!("{}'s frequency is not a number above 0", name))?;
+
+        let brain = match (get("brain"), get("behaviour")) {
+            (Ok(brain), Err(_)) => BrainSpec::parse(&brain)?,
+            (Err(_), Ok(tree)) => BrainSpec::Behaviour(Node::parse(&tree).map_err(|e| format!("{}: {}", name, e))?),
+            _ => return Err(format!("{} needs exactly one of brain or behaviour", name)),
+        };
+
+        templates.push(MonsterTemplate {
+            glyph: get("glyph")?.chars().next().ok_or(format!("{} has an empty glyph", name))?,
+            health: number("health")?,
+            damage: number("damage")?,
+            frequency,
+            brain,
+            name,
+        });
+    }
+
+    if templates.is_empty() {
+        return Err("no monsters defined".to_string())
+    }
+
+    // Monsters are picked by rolling under the total, which has to fit in a u32
+    match templates.iter().try_fold(0u32, |total, t| total.checked_add(t.frequency)) {
+        Some(total) if total > 0 => {}
+        _ => return Err("the frequencies don't add up to a usable total".to_string()),
+    }
+
+    Ok(templates)
+}
@@ -0,0 +1,130 @@
+use crate::game::Game;
+use crate::actor::Actor;
+use crate::item::{symbol_for_type, pile_symbol};
+use crate::util::capitalize;
+
+use std::fs;
+use std::path::Path;
+
+/// How many of the last messages go in the morgue file
+const MORGUE_MESSAGES: usize = 20;
+
+/// How the game ended, e.g. "killed by a kobold"
+pub fn cause_of_death(game: &Game) -> String {
+    let player = game.level.entities[0].as_ref().unwrap();
+    if !player.is_dead() {
+        return "quit".to_string()
+    }
+
+    player.last_hurt_by.as_ref().map_or("died".to_string(), |cause| cause.describe())
+}
+
+/// The end of game recap: how it ended, how far the player got, and what they had
+pub fn summary(game: &Game) -> Vec<String> {
+    let player = game.level.entities[0].as_ref().unwrap();
+
+    let mut lines = vec![
+        format!("{} on depth {} after {} turns.", capitalize(&cause_of_death(game)), game.depth, game.turns),
+        String::new(),
+        format!("Kills: {}", describe_kills(&game.kills)),
+        format!("Experience: {}", player.xp),
+        format!("Health: {}/{}", player.health.max(0), player.max_health),
+        format!("Gold: {}", player.gold),
+        String::new(),
+        format!("Inventory (weight {}/{}):", player.carried_weight(), player.capacity()),
+    ];
+
+    if player.inventory.is_empty() {
+        lines.push("  nothing".to_string());
+    }
+    for item in &player.inventory {
+        lines.push(format!("  {} {}", symbol_for_type(&item.item_type), item.describe()));
+        for inside in &item.contents {
+            lines.push(format!("      {} {}", symbol_for_type(&inside.item_type), inside.describe()));
+        }
+    }
+
+    lines
+}
+
+/// The kills as a count of each kind of monster, e.g. "3 (2 kobold, 1 jackal)"
+fn describe_kills(kills: &[String]) -> String {
+    if kills.is_empty() {
+        return "none".to_string()
+    }
+
+    // Counted in the order each kind was first killed
+    let mut counts: Vec<(&str, u32)> = vec![];
+    for kill in kills {
+        match counts.iter_mut().find(|(name, _)| name == kill) {
+            Some(count) => count.1 += 1,
+            None => counts.push((kill, 1)),
+        }
+    }
+
+    let counts = counts.iter().map(|(name, count)| format!("{} {}", count, name)).collect::<Vec<_>>();
+    format!("{} ({})", kills.len(), counts.join(", "))
+}
+
+/// The level as the player knew it: explored terrain, with what's standing
+/// and lying on it. Unexplored tiles are blank, and so are left out at the top and bottom.
+pub fn map_snapshot(game: &Game) -> Vec<String> {
+    let map = game.level.map_component.get_map();
+    let seen = |x: usize, y: usize| game.show_map || map[x][y].explored;
+
+    let mut grid = (0..map[0].len()).map(|y| {
+        (0..map.len()).map(|x| if seen(x, y) { map[x][y].apparent_kind().glyph() } else { ' ' }).collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+
+    for (position, items) in game.level.items.iter() {
+        if seen(position.x as usize, position.y as usize) {
+            grid[position.y as usize][position.x as usize] = pile_symbol(items);
+        }
+    }
+
+    // The player goes last so nothing is drawn over them
+    for entity in game.level.entities.iter().rev() {
+        let entity = entity.as_ref().unwrap();
+        let position = entity.get_position();
+        if seen(position.x as usize, position.y as usize) {
+            grid[position.y as usize][position.x as usize] = entity.display_char;
+        }
+    }
+
+    let rows = grid.into_iter().map(|row| row.into_iter().collect::<String>().trim_end().to_string()).collect::<Vec<_>>();
+    let first = rows.iter().position(|r| !r.is_empty()).unwrap_or(rows.len());
+    let last = rows.iter().rposition(|r| !r.is_empty()).map_or(first, |i| i + 1);
+
+    rows[first..last].to_vec()
+}
+
+/// The whole morgue file: a character sheet, the last messages and the final map
+pub fn contents(game: &Game) -> String {
+    let mut lines = vec![
+        "MR: TOM morgue file".to_string(),
+        format!("Seed: {}{}", game.seed, if game.settings.wizard { " (wizard mode)" } else { "" }),
+        String::new(),
+    ];
+    lines.extend(summary(game));
+
+    lines.push(String::new());
+    lines.push("Last messages:".to_string());
+    let messages = game.message_cache.iter().chain(game.message_queue.iter()).collect::<Vec<_>>();
+    let skip = messages.len().saturating_sub(MORGUE_MESSAGES);
+    lines.extend(messages.into_iter().skip(skip).map(|m| format!("  {}", m)));
+
+    lines.push(String::new());
+    lines.push(format!("Depth {}:", game.depth));
+    lines.extend(map_snapshot(game));
+
+    lines.join("\n") + "\n"
+}
+
+/// Writes the morgue file for the game to `path`
+pub fn write(game: &Game, path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, contents(game))
+}
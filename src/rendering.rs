@@ -1,6 +1,6 @@
 use crate::map::{MapComponent, Map};
 use crate::util::{Point, Bound};
-use crate::game::{MAP_WIDTH, MAP_HEIGHT, MAP_OFFSET, SHOW_MAP};
+use crate::game::MAP_OFFSET;
 use crate::actor::Actor;
 
 use tcod::Color;
@@ -24,7 +24,7 @@ pub trait RenderingComponent {
     /// Renders every explored tile in a `Map`
     fn render_map(&mut self, map: &mut Map, player: &Entity);
     /// Renders a specific explored tile
-    fn render_tile(&mut self, x: i32, y: i32, symbol: char, explored: &mut bool);
+    fn render_tile(&mut self, x: i32, y: i32, symbol: char, color: Color, explored: &mut bool);
     /// Renders a single object
     fn render_object(&mut self, point: Point, symbol: char);
     /// Writes a game message
@@ -37,8 +37,14 @@ pub trait RenderingComponent {
     fn after_render_new_frame(&mut self);
     /// Wait for keypresses in the console
     fn wait_for_keypress(&mut self) -> Key;
-    /// Returns the console object used for rendering
-    fn get_root_console(&self) -> &Root;
+    /// Whether the player has closed the game window
+    fn window_closed(&self) -> bool;
+    /// Sets whether the whole map is drawn, seen or not
+    fn set_show_map(&mut self, show: bool);
+    /// Starts drawing a different map, such as when the player changes level
+    fn set_map(&mut self, map_component: &Box<dyn MapComponent>);
+    /// Sets whether frames are kept off the screen, such as while a resumed game catches up
+    fn set_fast_forward(&mut self, on: bool);
 }
 
 /// The basic text rendering component which is used by default
@@ -47,6 +53,12 @@ pub struct TcodRenderingComponent {
     pub console: Root,
     /// The map corresponding to the character's FOV
     pub fov_map: FovMap,
+    /// Whether to draw the whole map, seen or not (wizard mode)
+    pub show_map: bool,
+    /// Whether the FOV must be recomputed even though the player hasn't moved
+    fov_stale: bool,
+    /// Whether frames are drawn without being shown
+    fast_forward: bool,
     prev_message: (String, i32),
     new_message: bool,
 }
@@ -54,31 +66,39 @@ pub struct TcodRenderingComponent {
 impl TcodRenderingComponent {
     /// Create a basic new rendering component.
     /// The `MapComponent` is needed for the initial calculation of the character's FOV
-    pub fn new(bounds: Bound, map_component: &Box<dyn MapComponent>) -> Self {
+    pub fn new(bounds: Bound, map_component: &Box<dyn MapComponent>, show_map: bool) -> Self {
         let console = Root::initializer()
             .size(bounds.max.x - 1, bounds.max.y - 1)
             .title("Monochrome Rogue-like: The Original Masterpiece")
             .fullscreen(false)
             .init();
 
+        TcodRenderingComponent {
+            console,
+            fov_map: Self::build_fov_map(map_component),
+            show_map,
+            fov_stale: true,
+            fast_forward: false,
+            prev_message: (String::new(), 0),
+            new_message: false,
+        }
+    }
+
+    fn build_fov_map(map_component: &Box<dyn MapComponent>) -> FovMap {
         let map = map_component.get_map();
 
         // Build FOV map
-        let mut fov_map = FovMap::new(MAP_WIDTH, MAP_HEIGHT);
-        for y in 0..MAP_HEIGHT {
-            for x in 0..MAP_WIDTH {
+        let (width, height) = (map.len() as i32, map[0].len() as i32);
+        let mut fov_map = FovMap::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
                 fov_map.set(x, y,
                             !map[x as usize][y as usize].block_sight,
                             !map[x as usize][y as usize].blocked);
             }
         }
 
-        TcodRenderingComponent {
-            console,
-            fov_map,
-            prev_message: (String::new(), 0),
-            new_message: false,
-        }
+        fov_map
     }
 }
 
@@ -91,20 +111,17 @@ impl RenderingComponent for TcodRenderingComponent {
     fn render_map(&mut self, map: &mut Map, player: &Entity) {
         // Recompute the FOV before we render the map
         let player_pos = player.get_position();
-        if player_pos != player.get_last_position() {
+        if player_pos != player.get_last_position() || self.fov_stale {
             self.fov_map.compute_fov(player_pos.x, player_pos.y, PLAYER_FOV, FOV_LIGHT_WALLS, FOV_ALGO);
+            self.fov_stale = false;
         }
 
         for x in 0..map.len() {
             for y in 0..map[x].len() {
-                let wall = map[x][y].block_sight;
+                let kind = map[x][y].apparent_kind();
                 let color_override = map[x][y].color_override;
 
-                if !wall {
-                    self.render_tile(x as i32, y as i32, '.', &mut map[x][y].explored);
-                } else {
-                    self.render_tile(x as i32, y as i32, '+', &mut map[x][y].explored);
-                }
+                self.render_tile(x as i32, y as i32, kind.glyph(), kind.color(), &mut map[x][y].explored);
 
                 if let Some(color) = color_override {
                     self.console.set_char_background(x as i32, y as i32 + MAP_OFFSET, color, BackgroundFlag::Set);
@@ -114,9 +131,10 @@ impl RenderingComponent for TcodRenderingComponent {
         }
     }
 
-    fn render_tile(&mut self, x: i32, y: i32, symbol: char, explored: &mut bool) {
-        if self.fov_map.is_in_fov(x, y) || SHOW_MAP {
+    fn render_tile(&mut self, x: i32, y: i32, symbol: char, color: Color, explored: &mut bool) {
+        if self.fov_map.is_in_fov(x, y) || self.show_map {
             self.console.put_char(x, y + MAP_OFFSET, symbol, BackgroundFlag::Set);
+            self.console.set_char_foreground(x, y + MAP_OFFSET, color);
             *explored = true;
         } else if *explored {
             self.console.put_char(x, y + MAP_OFFSET, symbol, BackgroundFlag::Set);
@@ -125,8 +143,9 @@ impl RenderingComponent for TcodRenderingComponent {
     }
 
     fn render_object(&mut self, position: Point, symbol: char) {
-        if self.fov_map.is_in_fov(position.x, position.y) || SHOW_MAP {
+        if self.fov_map.is_in_fov(position.x, position.y) || self.show_map {
             self.console.put_char(position.x, position.y + MAP_OFFSET, symbol, BackgroundFlag::Set);
+            self.console.set_char_foreground(position.x, position.y + MAP_OFFSET, Color { r: 255, g: 255, b: 255 });
         }
     }
 
@@ -159,15 +178,57 @@ impl RenderingComponent for TcodRenderingComponent {
         if !self.new_message {
             self.push_message_color(&self.prev_message.0.clone(), Color { r: 105, g: 105, b: 105 });
         }
-        self.console.flush();
+        if !self.fast_forward {
+            self.console.flush();
+        }
     }
 
     fn wait_for_keypress(&mut self) -> Key {
         self.console.wait_for_keypress(true)
     }
 
-    fn get_root_console(&self) -> &Root {
-        &self.console
+    fn window_closed(&self) -> bool {
+        self.console.window_closed()
     }
+
+    fn set_show_map(&mut self, show: bool) {
+        self.show_map = show;
+    }
+
+    fn set_map(&mut self, map_component: &Box<dyn MapComponent>) {
+        self.fov_map = Self::build_fov_map(map_component);
+        self.fov_stale = true;
+    }
+
+    fn set_fast_forward(&mut self, on: bool) {
+        self.fast_forward = on;
+    }
+}
+
+/// A rendering component that draws nothing, for running replays without a window
+pub struct HeadlessRenderingComponent;
+
+impl RenderingComponent for HeadlessRenderingComponent {
+    fn before_render_new_frame(&mut self) {}
+    fn render_map(&mut self, _map: &mut Map, _player: &Entity) {}
+    fn render_tile(&mut self, _x: i32, _y: i32, _symbol: char, _color: Color, _explored: &mut bool) {}
+    fn render_object(&mut self, _point: Point, _symbol: char) {}
+    fn push_message(&mut self, _message: &String) {}
+    fn push_message_color(&mut self, _message: &String, _color: Color) {}
+    fn print(&mut self, _message: &String, _x: i32, _y: i32) {}
+    fn after_render_new_frame(&mut self) {}
+
+    /// There's no keyboard without a window; the game feeds itself from its replay instead
+    fn wait_for_keypress(&mut self) -> Key {
+        Key::default()
+    }
+
+    fn window_closed(&self) -> bool {
+        false
+    }
+
+    fn set_show_map(&mut self, _show: bool) {}
+    fn set_map(&mut self, _map_component: &Box<dyn MapComponent>) {}
+    fn set_fast_forward(&mut self, _on: bool) {}
 }
 
@@ -0,0 +1,202 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Error as IOE};
+
+use crate::game::GameSettings;
+use crate::map::Generator;
+use crate::keymap::{self, Keymap};
+use crate::monster::{self, Catalogue};
+
+use tcod::input::Key;
+
+/// A game's seed, settings, keymap and every key the player pressed, which
+/// is all it takes to play the game back exactly as it happened.
+///
+/// On disk it's a text file: a `seed <n>` line, then the settings as
+/// `size <width> <height>`, `generator <name>` and `wizard` lines, a
+/// `monsters <hash>` line, the keymap as in a keymap file, then one key per
+/// line as its code, the printable character's number, and any held
+/// modifiers, e.g. `Char 103 shift`.
+#[derive(Clone)]
+pub struct Replay {
+    pub seed: u64,
+    pub settings: GameSettings,
+    /// The hash of the monster catalogue the game was played with, which it only
+    /// plays back the same with. Older replays didn't keep it.
+    pub monsters: Option<u64>,
+    pub keymap: Keymap,
+    pub keys: Vec<Key>,
+}
+
+/// Feeds the keys of a `Replay` back to the game one at a time
+pub struct Playback {
+    keys: VecDeque<Key>,
+    /// How long to linger on each key, so a watched replay can be followed
+    pub delay: Duration,
+    /// Whether the game is running without a window
+    pub headless: bool,
+}
+
+impl Replay {
+    /// Starts an empty recording of the game with the given seed, settings, monster catalogue and keymap
+    pub fn new(seed: u64, settings: GameSettings, monsters: &Catalogue, keymap: Keymap) -> Replay {
+        Replay { seed, settings, monsters: Some(monsters.hash), keymap, keys: vec![] }
+    }
+
+    /// Checks the game was played with `monsters`, as it only plays back the same with them
+    pub fn check_monsters(&self, monsters: &Catalogue) -> Result<(), String> {
+        match self.monsters {
+            Some(hash) if hash != monsters.hash => Err(format!("the game was played with a different monster catalogue ({})", monster::MONSTER_FILE)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads a replay file
+    pub fn load(path: &Path) -> std::io::Result<Replay> {
+        Self::parse(&fs::read_to_string(path)?).map_err(|e| IOE::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Reads a replay from the text of a replay file
+    pub fn parse(contents: &str) -> Result<Replay, String> {
+        let mut lines = contents.lines().filter(|l| !l.trim().is_empty());
+
+        let seed = lines.next()
+            .and_then(|l| {
+                let mut words = l.split_whitespace();
+                if words.next() != Some("seed") { return None }
+                words.next().and_then(|s| s.parse::<u64>().ok())
+            })
+            .ok_or("replay doesn't start with a seed".to_string())?;
+
+        // Older replays have no settings, and were all played with the defaults
+        let mut settings = GameSettings::default();
+        let mut monsters = None;
+        let mut keymap_lines = vec![];
+        let mut keys = vec![];
+        for (i, line) in lines.enumerate() {
+            if parse_setting(line, &mut settings) {
+                continue
+            }
+
+            if line.starts_with("monsters ") {
+                let hash = u64::from_str_radix(line["monsters ".len()..].trim(), 16);
+                monsters = Some(hash.map_err(|_| format!("bad monster catalogue hash on line {}: {}", i + 2, line))?);
+                continue
+            }
+
+            if line.starts_with("preset ") || line.starts_with("bind ") {
+                keymap_lines.push(line);
+                continue
+            }
+
+            let key = parse_key(line).ok_or(format!("bad key on line {}: {}", i + 2, line))?;
+            keys.push(key);
+        }
+
+        let (keymap, _) = Keymap::parse(&keymap_lines.join("\n"));
+        Ok(Replay { seed, settings, monsters, keymap, keys })
+    }
+
+    /// Writes the replay out to `path`
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// The replay as the text of a replay file
+    pub fn to_text(&self) -> String {
+        let mut contents = format!("seed {}\n", self.seed);
+        contents.push_str(&format!("size {} {}\n", self.settings.width, self.settings.height));
+        contents.push_str(&format!("generator {}\n", self.settings.generator.name()));
+        if self.settings.wizard {
+            contents.push_str("wizard\n");
+        }
+        if let Some(hash) = self.monsters {
+            contents.push_str(&format!("monsters {:016x}\n", hash));
+        }
+        for line in self.keymap.to_lines() {
+            contents.push_str(&line);
+            contents.push('\n');
+        }
+
+        for key in &self.keys {
+            contents.push_str(&format_key(key));
+            contents.push('\n');
+        }
+
+        contents
+    }
+}
+
+impl Playback {
+    pub fn new(replay: Replay, delay: Duration, headless: bool) -> Playback {
+        Playback { keys: replay.keys.into_iter().collect(), delay, headless }
+    }
+
+    /// The next recorded key, or `None` once the replay has run out
+    pub fn next_key(&mut self) -> Option<Key> {
+        let key = self.keys.pop_front()?;
+        if !self.headless {
+            std::thread::sleep(self.delay);
+        }
+
+        Some(key)
+    }
+
+    /// Whether every key has been played back
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Reads a settings line into `settings`, returning whether it was one
+fn parse_setting(line: &str, settings: &mut GameSettings) -> bool {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+
+    match words.as_slice() {
+        ["size", width, height] => match (width.parse::<i32>(), height.parse::<i32>()) {
+            (Ok(w), Ok(h)) => { settings.width = w; settings.height = h; true }
+            _ => false,
+        }
+        ["generator", name] => match Generator::from_name(name) {
+            Some(generator) => { settings.generator = generator; true }
+            None => false,
+        }
+        ["wizard"] => { settings.wizard = true; true }
+        _ => false,
+    }
+}
+
+/// A key as it's written in a replay file, e.g. `Char 103 shift`
+pub fn format_key(key: &Key) -> String {
+    let mut line = format!("{:?} {}", key.code, key.printable as u32);
+    if key.shift { line.push_str(" shift") }
+    if key.ctrl { line.push_str(" ctrl") }
+    if key.alt { line.push_str(" alt") }
+    line
+}
+
+fn parse_key(line: &str) -> Option<Key> {
+    let mut words = line.split_whitespace();
+
+    let name = words.next()?;
+    let code = keymap::key_code_from_name(name)?;
+    let printable = std::char::from_u32(words.next()?.parse::<u32>().ok()?)?;
+
+    let mut key = Key::default();
+    key.code = code;
+    key.printable = printable;
+    key.pressed = true;
+
+    for modifier in words {
+        match modifier {
+            "shift" => key.shift = true,
+            "ctrl" => key.ctrl = true,
+            "alt" => key.alt = true,
+            _ => return None,
+        }
+    }
+
+    Some(key)
+}
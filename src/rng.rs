@@ -0,0 +1,43 @@
+use rand_isaac::IsaacRng;
+use rand_core::SeedableRng;
+
+/// The separate random number streams used by each part of the game.
+/// Every stream is derived from the game's seed, so a seed and the same
+/// sequence of inputs always play out the same way. Keeping them apart means
+/// that, say, a monster making an extra decision doesn't change the layout of
+/// the next level.
+pub struct RngStreams {
+    /// Level layouts, terrain, traps and monster placement
+    pub mapgen: IsaacRng,
+    /// Rolls made while resolving actions: fights, traps and searching
+    pub combat: IsaacRng,
+    /// Monster decisions and perception
+    pub ai: IsaacRng,
+    /// What items are generated and where
+    pub loot: IsaacRng,
+}
+
+impl RngStreams {
+    /// Derives every stream from the game's seed
+    pub fn from_seed(seed: u64) -> RngStreams {
+        // Odd constants spread the streams' seeds far apart from one another
+        RngStreams {
+            mapgen: IsaacRng::seed_from_u64(seed),
+            combat: IsaacRng::seed_from_u64(seed ^ 0x9e37_79b9_7f4a_7c15),
+            ai: IsaacRng::seed_from_u64(seed ^ 0xbf58_476d_1ce4_e5b9),
+            loot: IsaacRng::seed_from_u64(seed ^ 0x94d0_49bb_1331_11eb),
+        }
+    }
+
+    /// Derives the streams for the given depth of the dungeon, so each level
+    /// plays out the same however the player got there
+    pub fn for_depth(seed: u64, depth: u32) -> RngStreams {
+        Self::from_seed(seed.wrapping_add((depth as u64 - 1).wrapping_mul(0x2545_f491_4f6c_dd1d)))
+    }
+}
+
+/// The stream that decides what unidentified items look like. It's drawn from
+/// once, when the game starts, so appearances stay the same at every depth.
+pub fn appearances(seed: u64) -> IsaacRng {
+    IsaacRng::seed_from_u64(seed ^ 0xd6e8_feb8_6659_fd93)
+}
@@ -0,0 +1,422 @@
+use crate::game::Game;
+use crate::config::{config_dir, config_path};
+use crate::replay::Replay;
+use crate::util::hash;
+
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// The version of the save format written by this build
+pub const SAVE_VERSION: u32 = 3;
+/// The version of what this build makes from a seed. Saves are played back from their seed,
+/// so this has to be bumped whenever level generation changes, or old saves would play back
+/// into different levels.
+pub const CONTENT_VERSION: u32 = 1;
+/// The first line of every save file after the first version
+const SAVE_HEADER: &str = "mrtom save";
+/// The extension given to save slot files
+const SAVE_EXTENSION: &str = "sav";
+/// The extension given to autosaves, which are deleted when the game exits cleanly
+const AUTOSAVE_EXTENSION: &str = "autosave";
+/// Where saves went before there were slots, relative to the config directory
+const LEGACY_SAVE: &str = "save.dat";
+
+/// What's kept of a game between sessions. Nothing about the levels themselves is kept,
+/// such as what's in containers or whether they're locked: all of it is rebuilt by playing
+/// the replay back, which is why the content version has to match.
+#[derive(Clone)]
+pub struct SaveData {
+    pub seed: u64,
+    /// The `CONTENT_VERSION` of the build the game was played on. Saves from before version 3 don't say.
+    pub content: Option<u32>,
+    /// Whether wizard mode was used; once a wizard, always a wizard
+    pub wizard: bool,
+    /// How deep the player was
+    pub depth: u32,
+    /// How many turns had been played
+    pub turns: u32,
+    /// Every key pressed so far, which the game is caught back up with on loading.
+    /// Saves from before version 2 only kept the seed, and start over from it.
+    pub replay: Option<Replay>,
+}
+
+impl SaveData {
+    pub fn new(game: &Game) -> SaveData {
+        SaveData {
+            seed: game.seed,
+            content: Some(CONTENT_VERSION),
+            wizard: game.settings.wizard,
+            depth: game.depth,
+            turns: game.turns,
+            replay: Some(game.recording.clone()),
+        }
+    }
+
+    /// Checks the game was made from the same content as this build makes, so it plays back the same
+    pub fn check_content(&self) -> Result<(), String> {
+        match self.content {
+            Some(CONTENT_VERSION) => Ok(()),
+            Some(v) => Err(format!("the game was made by a build whose levels differ (content {}, this build makes {})", v, CONTENT_VERSION)),
+            None => Err("the save is from a build whose levels may differ (it has no content version)".to_string()),
+        }
+    }
+}
+
+/// A named place to save a game
+#[derive(Clone, Debug, PartialEq)]
+pub struct Slot {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Slot {
+    /// The slot called `name` in the saves directory. Characters that don't
+    /// belong in a file name are replaced.
+    pub fn named(name: &str) -> Option<Slot> {
+        let file_name = name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' { c } else { '_' }).collect::<String>();
+        Some(Slot { name: name.to_string(), path: saves_dir()?.join(format!("{}.{}", file_name, SAVE_EXTENSION)) })
+    }
+
+    /// A slot at a particular path, such as one given on the command line
+    pub fn at(path: &Path) -> Slot {
+        let name = path.file_stem().map_or("save".to_string(), |s| s.to_string_lossy().into_owned());
+        Slot { name, path: path.to_path_buf() }
+    }
+
+    /// Where the slot's autosave goes. One left behind means the game didn't exit cleanly.
+    pub fn autosave_path(&self) -> PathBuf {
+        self.path.with_extension(AUTOSAVE_EXTENSION)
+    }
+
+    /// Reads the game saved in the slot
+    pub fn load(&self) -> Result<SaveData, String> {
+        load(&self.path)
+    }
+
+    /// Reads the slot's autosave
+    pub fn load_autosave(&self) -> Result<SaveData, String> {
+        load(&self.autosave_path())
+    }
+
+    /// Saves a game into the slot, and clears away its autosave
+    pub fn write(&self, data: &SaveData) -> std::io::Result<()> {
+        write_atomically(&self.path, &encode(data))?;
+        remove(&self.autosave_path())
+    }
+
+    /// Empties the slot, autosave and all, such as when its game has ended
+    pub fn delete(&self) -> std::io::Result<()> {
+        remove(&self.path)?;
+        remove(&self.autosave_path())
+    }
+
+    /// Whether there's a save in the slot
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+}
+
+/// Writes `contents` next to `path` and swaps it in, so a crash part way
+/// through leaves whatever was at `path` intact
+pub fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut temp = path.as_os_str().to_os_string();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+
+    let mut file = File::create(&temp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+
+    fs::rename(&temp, path)
+}
+
+fn load(path: &Path) -> Result<SaveData, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    decode(&contents)
+}
+
+/// Removes a file, if it's there
+fn remove(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn saves_dir() -> Option<PathBuf> {
+    Some(config_dir()?.join("saves"))
+}
+
+/// Every save slot with something in it, in order of name. The save from
+/// before there were slots is included if it's still around.
+pub fn slots() -> Result<Vec<Slot>, String> {
+    let dir = saves_dir().ok_or("Could not find a config directory to save in".to_string())?;
+
+    let mut slots = vec![];
+    match fs::read_dir(&dir) {
+        Ok(entries) => for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.extension().map_or(false, |e| e == SAVE_EXTENSION) {
+                slots.push(Slot::at(&path));
+            }
+        }
+        Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Could not read {}: {}", dir.display(), e.to_string())),
+    }
+    slots.sort_by(|a, b| a.name.cmp(&b.name));
+
+    // Scrubbed legacy saves were left empty rather than deleted
+    if let Some(legacy) = config_path(LEGACY_SAVE) {
+        if fs::metadata(&legacy).map_or(false, |m| m.len() > 0) {
+            slots.push(Slot::at(&legacy));
+        }
+    }
+
+    Ok(slots)
+}
+
+/// Every slot with an autosave left behind by a game that didn't exit cleanly
+pub fn interrupted() -> Vec<Slot> {
+    let mut slots = saves_dir().and_then(|dir| fs::read_dir(dir).ok()).map_or(vec![], |entries| {
+        entries.filter_map(|e| e.ok()).map(|e| e.path())
+            .filter(|p| p.extension().map_or(false, |e| e == AUTOSAVE_EXTENSION))
+            .map(|p| Slot::at(&p.with_extension(SAVE_EXTENSION)))
+            .collect()
+    });
+    slots.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if let Some(legacy) = config_path(LEGACY_SAVE).map(|p| Slot::at(&p)) {
+        if legacy.autosave_path().exists() {
+            slots.push(legacy);
+        }
+    }
+
+    slots
+}
+
+/// A slot name that isn't taken yet: "game 1", "game 2" and so on
+pub fn unused_name() -> String {
+    (1..).map(|i| format!("game {}", i))
+        .find(|name| Slot::named(name).map_or(true, |s| !s.exists() && !s.autosave_path().exists()))
+        .unwrap()
+}
+
+/// Writes out a save in the current format, checksum last
+pub fn encode(data: &SaveData) -> String {
+    let mut body = format!("{} {}\nseed {}\n", SAVE_HEADER, SAVE_VERSION, data.seed);
+    if let Some(content) = data.content {
+        body.push_str(&format!("content {}\n", content));
+    }
+    if data.wizard {
+        body.push_str("wizard\n");
+    }
+    body.push_str(&format!("depth {}\nturns {}\n", data.depth, data.turns));
+    if let Some(replay) = &data.replay {
+        body.push_str("replay\n");
+        body.push_str(&replay.to_text());
+    }
+
+    let sum = hash(body.as_bytes());
+    format!("{}checksum {:016x}\n", body, sum)
+}
+
+/// Reads a save in any format there has been, checking it hasn't been corrupted
+pub fn decode(contents: &str) -> Result<SaveData, String> {
+    verify(contents)?;
+    decode_unverified(contents)
+}
+
+/// Reads a save without checking its checksum, for looking into corrupted saves
+pub fn decode_unverified(contents: &str) -> Result<SaveData, String> {
+    match version(contents)? {
+        0 => decode_v0(contents),
+        1 | 2 | 3 => decode_lines(split_checksum(contents).0),
+        v => Err(too_new(v)),
+    }
+}
+
+/// Checks the save's checksum, for the formats that have one
+pub fn verify(contents: &str) -> Result<(), String> {
+    match version(contents)? {
+        0 => return Ok(()),
+        v if v > SAVE_VERSION => return Err(too_new(v)),
+        _ => {}
+    }
+
+    let (body, sum) = split_checksum(contents);
+    let sum = sum.ok_or("the save has no checksum".to_string())?;
+    let expected = u64::from_str_radix(sum, 16).map_err(|_| "the save's checksum is damaged".to_string())?;
+    if hash(body.as_bytes()) != expected {
+        return Err("the save is corrupted (its checksum doesn't match)".to_string())
+    }
+
+    Ok(())
+}
+
+/// Which version of the save format `contents` is in. The first version had no header.
+pub fn version(contents: &str) -> Result<u32, String> {
+    let first = contents.lines().next().ok_or("the save is empty".to_string())?;
+    if !first.starts_with(SAVE_HEADER) {
+        return Ok(0)
+    }
+
+    first[SAVE_HEADER.len()..].trim().parse::<u32>().map_err(|_| "the save's header is damaged".to_string())
+}
+
+fn too_new(version: u32) -> String {
+    format!("the save is from a newer version of the game (format {})", version)
+}
+
+/// The first format: the seed, then "wizard" on the next line if wizard mode was used
+fn decode_v0(contents: &str) -> Result<SaveData, String> {
+    let mut lines = contents.lines();
+    let seed = lines.next().and_then(|l| l.trim().parse::<u64>().ok()).ok_or("the save's seed is damaged".to_string())?;
+    let wizard = lines.next().map(|l| l.trim()) == Some("wizard");
+
+    Ok(SaveData { seed, content: None, wizard, depth: 1, turns: 0, replay: None })
+}
+
+/// Splits a save into everything before the checksum line, and the checksum if there is one
+fn split_checksum(contents: &str) -> (&str, Option<&str>) {
+    match contents.rfind("checksum ") {
+        Some(at) => (&contents[..at], Some(contents[at + "checksum ".len()..].trim())),
+        None => (contents, None),
+    }
+}
+
+/// A header then `key value` lines, which the checksum comes after.
+/// Version 2 adds the depth and turns, and ends the lines with the game's replay.
+/// Version 3 adds the content version.
+fn decode_lines(body: &str) -> Result<SaveData, String> {
+    let mut data = SaveData { seed: 0, content: None, wizard: false, depth: 1, turns: 0, replay: None };
+    let mut seed = None;
+    let mut lines = body.lines().skip(1);
+    while let Some(line) = lines.next() {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["seed", n] => seed = n.parse::<u64>().ok(),
+            ["content", n] => data.content = Some(n.parse::<u32>().map_err(|_| "the save's content version is damaged".to_string())?),
+            ["wizard"] => data.wizard = true,
+            ["depth", n] => data.depth = n.parse::<u32>().map_err(|_| "the save's depth is damaged".to_string())?,
+            ["turns", n] => data.turns = n.parse::<u32>().map_err(|_| "the save's turn count is damaged".to_string())?,
+            ["replay"] => {
+                // The replay is the rest of the save
+                let rest = lines.by_ref().collect::<Vec<_>>().join("\n");
+                data.replay = Some(Replay::parse(&rest).map_err(|e| format!("the save's replay is damaged: {}", e))?);
+            }
+            _ => return Err(format!("the save has an unknown line: {}", line)),
+        }
+    }
+
+    data.seed = seed.ok_or("the save has no seed".to_string())?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameSettings;
+    use crate::keymap::Keymap;
+
+    use tcod::input::{Key, KeyCode};
+
+    fn replay() -> Replay {
+        let mut keys = vec![Key::default(); 2];
+        keys[0].code = KeyCode::Char;
+        keys[0].printable = 'g';
+        keys[0].shift = true;
+        keys[1].code = KeyCode::Enter;
+
+        let settings = GameSettings { wizard: true, ..GameSettings::default() };
+        Replay { seed: 1234, settings, monsters: Some(0xfeed), keymap: Keymap::default(), keys }
+    }
+
+    fn data() -> SaveData {
+        SaveData { seed: 1234, content: Some(CONTENT_VERSION), wizard: true, depth: 3, turns: 42, replay: Some(replay()) }
+    }
+
+    /// A save body with a good checksum on the end, however broken the body is
+    fn sign(body: &str) -> String {
+        format!("{}checksum {:016x}\n", body, hash(body.as_bytes()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let contents = encode(&data());
+        assert_eq!(version(&contents), Ok(SAVE_VERSION));
+
+        let decoded = decode(&contents).unwrap();
+        assert_eq!((decoded.seed, decoded.wizard, decoded.depth, decoded.turns), (1234, true, 3, 42));
+        assert_eq!(decoded.content, Some(CONTENT_VERSION));
+        assert!(decoded.check_content().is_ok());
+
+        let replay = decoded.replay.unwrap();
+        assert_eq!(replay.to_text(), self::replay().to_text());
+        assert_eq!(replay.keys.len(), 2);
+        assert_eq!(replay.monsters, Some(0xfeed));
+    }
+
+    #[test]
+    fn decodes_v0() {
+        let contents = "1234\nwizard\n";
+        assert_eq!(version(contents), Ok(0));
+
+        let decoded = decode(contents).unwrap();
+        assert_eq!((decoded.seed, decoded.wizard, decoded.depth, decoded.turns), (1234, true, 1, 0));
+        assert!(decoded.replay.is_none());
+        assert!(decoded.check_content().is_err());
+        assert!(!decode("1234\n").unwrap().wizard);
+    }
+
+    #[test]
+    fn content_version() {
+        let other = encode(&SaveData { content: Some(CONTENT_VERSION + 1), ..data() });
+        assert!(decode(&other).unwrap().check_content().is_err());
+
+        // Saves from before there was a content version can't be trusted to play back the same
+        let body = format!("{} 2\nseed 1234\ndepth 3\nturns 42\nreplay\n{}", SAVE_HEADER, replay().to_text());
+        let old = decode(&sign(&body)).unwrap();
+        assert_eq!(old.content, None);
+        assert!(old.check_content().is_err());
+
+        let damaged = sign(&format!("{} {}\nseed 1\ncontent new\n", SAVE_HEADER, SAVE_VERSION));
+        assert_eq!(decode(&damaged).err(), Some("the save's content version is damaged".to_string()));
+    }
+
+    #[test]
+    fn flipped_byte_fails_verify() {
+        let contents = encode(&data()).replacen("turns 42", "turns 43", 1);
+        assert!(verify(&contents).is_err());
+        assert!(decode(&contents).is_err());
+        assert!(decode_unverified(&contents).is_ok());
+    }
+
+    #[test]
+    fn missing_checksum() {
+        let contents = encode(&data());
+        let body = &contents[..contents.rfind("checksum ").unwrap()];
+        assert_eq!(verify(body), Err("the save has no checksum".to_string()));
+    }
+
+    #[test]
+    fn newer_format() {
+        let contents = sign(&format!("{} {}\nseed 1\n", SAVE_HEADER, SAVE_VERSION + 1));
+        assert_eq!(version(&contents), Ok(SAVE_VERSION + 1));
+        assert_eq!(decode(&contents).err(), Some(too_new(SAVE_VERSION + 1)));
+        assert_eq!(decode_unverified(&contents).err(), Some(too_new(SAVE_VERSION + 1)));
+    }
+
+    #[test]
+    fn damaged_depth_and_turns() {
+        let depth = sign(&format!("{} {}\nseed 1\ndepth deep\nturns 4\n", SAVE_HEADER, SAVE_VERSION));
+        assert_eq!(decode(&depth).err(), Some("the save's depth is damaged".to_string()));
+
+        let turns = sign(&format!("{} {}\nseed 1\ndepth 2\nturns -4\n", SAVE_HEADER, SAVE_VERSION));
+        assert_eq!(decode(&turns).err(), Some("the save's turn count is damaged".to_string()));
+    }
+}
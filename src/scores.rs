@@ -0,0 +1,118 @@
+use crate::game::Game;
+use crate::config::{config_path, now, format_date};
+use crate::morgue;
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Error as IOE, Write};
+use std::path::PathBuf;
+
+/// How many scores the scoreboard shows
+pub const TOP_SCORES: usize = 10;
+
+/// Points for each level below the first the player reached
+const DEPTH_POINTS: u32 = 100;
+/// Points for each point of experience
+const XP_POINTS: u32 = 10;
+/// How many turns survived are worth a point
+const TURNS_PER_POINT: u32 = 10;
+
+/// One finished game on the high score table
+#[derive(Clone, Debug, PartialEq)]
+pub struct Score {
+    pub points: u32,
+    pub depth: u32,
+    pub xp: u32,
+    pub gold: u32,
+    pub turns: u32,
+    /// How the game ended, e.g. "killed by a kobold"
+    pub cause: String,
+    pub seed: u64,
+    /// When the game ended, in seconds since the Unix epoch
+    pub time: u64,
+    /// Whether wizard mode was used, which keeps the game off the scoreboard
+    pub wizard: bool,
+}
+
+impl Score {
+    /// The score for a finished game
+    pub fn new(game: &Game) -> Score {
+        let player = game.level.entities[0].as_ref().unwrap();
+        let gold = player.gold;
+
+        Score {
+            points: (game.depth - 1) * DEPTH_POINTS + player.xp * XP_POINTS + gold + game.turns / TURNS_PER_POINT,
+            depth: game.depth,
+            xp: player.xp,
+            gold,
+            turns: game.turns,
+            cause: morgue::cause_of_death(game),
+            seed: game.seed,
+            time: now(),
+            wizard: game.settings.wizard,
+        }
+    }
+
+    /// Reads a score from a line of the high score file
+    fn parse(line: &str) -> Option<Score> {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        if fields.len() != 9 {
+            return None
+        }
+
+        Some(Score {
+            points: fields[0].parse().ok()?,
+            depth: fields[1].parse().ok()?,
+            xp: fields[2].parse().ok()?,
+            gold: fields[3].parse().ok()?,
+            turns: fields[4].parse().ok()?,
+            seed: fields[5].parse().ok()?,
+            time: fields[6].parse().ok()?,
+            wizard: fields[7] == "wizard",
+            cause: fields[8].to_string(),
+        })
+    }
+
+    /// The score as a line of the high score file
+    fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", self.points, self.depth, self.xp, self.gold, self.turns,
+            self.seed, self.time, if self.wizard { "wizard" } else { "-" }, self.cause)
+    }
+
+    /// The score as a row of the scoreboard
+    pub fn describe(&self) -> String {
+        format!("{:>6}  depth {:<2} {:<28} seed {:<20} {}{}", self.points, self.depth, self.cause, self.seed,
+            format_date(self.time), if self.wizard { " (wizard)" } else { "" })
+    }
+}
+
+fn scores_path() -> std::io::Result<PathBuf> {
+    config_path("scores.txt").ok_or(IOE::from(ErrorKind::NotFound))
+}
+
+/// Every score recorded so far, in the order the games were played.
+/// Lines that can't be read are skipped.
+pub fn load() -> std::io::Result<Vec<Score>> {
+    match fs::read_to_string(scores_path()?) {
+        Ok(contents) => Ok(contents.lines().filter_map(Score::parse).collect()),
+        Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e),
+    }
+}
+
+/// Adds a score to the high score file
+pub fn record(score: &Score) -> std::io::Result<()> {
+    let path = scores_path()?;
+    fs::create_dir_all(path.parent().unwrap())?;
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", score.to_line())
+}
+
+/// The best scores, highest first. Wizard games don't count.
+pub fn top(scores: &[Score], n: usize) -> Vec<Score> {
+    let mut top = scores.iter().filter(|s| !s.wizard).cloned().collect::<Vec<_>>();
+    // Ties go to whoever got there first
+    top.sort_by(|a, b| b.points.cmp(&a.points).then(a.time.cmp(&b.time)));
+    top.truncate(n);
+    top
+}
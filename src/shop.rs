@@ -0,0 +1,250 @@
+use crate::game::Game;
+use crate::state::{State, MenuState, ItemSelectState};
+use crate::actor::{Actor, Entity};
+use crate::brain::ShopkeeperBrainComponent;
+use crate::item::{self, Item, ItemType, ItemsMap};
+use crate::map::{MapComponent, Rect, TileKind};
+use crate::util::Point;
+
+use rand::Rng;
+use rand_isaac::IsaacRng;
+
+/// What shopkeepers are called, and how they're found on the level
+pub const SHOPKEEPER_NAME: &str = "shopkeeper";
+/// The chance of a level with more than one room having a shop
+const SHOP_CHANCE: f64 = 1.0 / 2.0;
+/// How many items a shop is stocked with, at least and at most
+const STOCK: (u32, u32) = (4, 8);
+/// How many random spots are tried for each thing put in the shop before giving up on it
+const MAX_PLACEMENT_TRIES: u32 = 10;
+/// How tough a shopkeeper is, so robbing one is a bad idea
+const SHOPKEEPER_HEALTH: i32 = 40;
+const SHOPKEEPER_DAMAGE: i32 = 6;
+/// How much less than its price a shopkeeper pays for an item
+const SELL_DIVISOR: u32 = 2;
+
+/// A room whose floor is given over to things for sale, minded by a shopkeeper.
+/// What lies in the room when it's stocked is for sale, along with what's in chests
+/// there and what the player sells. Anything taken from the stock is owed for
+/// until it's paid for, and leaving without paying makes the shopkeeper hostile.
+pub struct Shop {
+    pub room: Rect,
+    /// Whether the player has robbed the shop
+    pub robbed: bool,
+}
+
+impl Shop {
+    /// Whether `point` is on the shop floor
+    pub fn contains(&self, point: Point) -> bool {
+        on_floor(&self.room, point)
+    }
+}
+
+/// Whether `point` is inside the walls of `room`
+fn on_floor(room: &Rect, point: Point) -> bool {
+    point.x > room.x1 && point.x < room.x2 && point.y > room.y1 && point.y < room.y2
+}
+
+/// Maybe turns one of the level's rooms into a shop, stocking it and putting a shopkeeper in it.
+/// The room the player starts in is never a shop.
+pub fn generate(mc: &mut Box<dyn MapComponent>, items: &mut ItemsMap, random: &mut IsaacRng) -> Option<(Shop, Entity)> {
+    let start = mc.get_player_start();
+    let rooms = mc.get_rooms().iter().cloned().filter(|room| !on_floor(room, start)).collect::<Vec<_>>();
+    if rooms.is_empty() || !random.gen_bool(SHOP_CHANCE) {
+        return None
+    }
+
+    let shop = Shop { room: rooms[random.gen_range(0, rooms.len())], robbed: false };
+
+    let wares = item::catalogue().iter().filter(|t| t.item_type != ItemType::COIN && t.name != item::CHEST).collect::<Vec<_>>();
+    for _ in 0..random.gen_range(STOCK.0, STOCK.1 + 1) {
+        let template = wares[random.gen_range(0, wares.len())];
+        if let Some(point) = free_floor(mc, &shop.room, random) {
+            item::add_to_pile(items.entry(point).or_insert_with(Vec::new), template.spawn(point));
+        }
+    }
+
+    // Whatever was already lying in the room is the shop's too
+    for (_, pile) in items.iter_mut().filter(|(point, _)| shop.contains(**point)) {
+        pile.iter_mut().for_each(|item| item.put_up_for_sale());
+    }
+
+    let at = free_floor(mc, &shop.room, random)?;
+    mc.get_map_mut()[at.x as usize][at.y as usize].occupied = true;
+    let brain = box ShopkeeperBrainComponent::new(&shop.room);
+    let keeper = Entity::new(SHOPKEEPER_NAME, at.x, at.y, SHOPKEEPER_HEALTH, SHOPKEEPER_DAMAGE, '@', brain, false);
+
+    Some((shop, keeper))
+}
+
+/// A plain floor tile in `room` that nobody is standing on, if one turns up
+fn free_floor(mc: &Box<dyn MapComponent>, room: &Rect, random: &mut IsaacRng) -> Option<Point> {
+    (0..MAX_PLACEMENT_TRIES).map(|_| room.rand_point(random)).find(|point| {
+        let tile = mc.get_map()[point.x as usize][point.y as usize];
+        tile.kind == TileKind::Floor && !tile.blocked && !tile.occupied
+    })
+}
+
+/// What the shopkeeper offers when the player bumps into them: buying what's
+/// on the shop floor, selling from the pack, and settling the bill
+pub fn menu(game: &Game) -> Box<dyn State> {
+    let player = game.level.entities[0].as_ref().unwrap();
+    let bill = player.bill();
+
+    let mut options = vec!["Buy".to_string(), "Sell".to_string()];
+    if bill > 0 {
+        options.push(format!("Pay your bill of {} gold", bill));
+    }
+
+    let title = format!("\"Welcome! What can I do for you?\" (you have {} gold)", player.gold);
+    box MenuState::new(&title, options, box |game, choice| {
+        match choice {
+            0 => buy_menu(game),
+            1 => sell_menu(game),
+            _ => {
+                pay(game);
+                None
+            }
+        }
+    })
+}
+
+/// Everything for sale on the shop floor, in order of where it lies
+fn stock(game: &Game) -> Vec<(Point, usize)> {
+    let shop = match &game.level.shop {
+        Some(shop) => shop,
+        None => return vec![],
+    };
+
+    let mut points = game.level.items.keys().cloned().filter(|&p| shop.contains(p)).collect::<Vec<_>>();
+    points.sort();
+    points.into_iter().flat_map(|p| {
+        let pile = &game.level.items[&p];
+        (0..pile.len()).filter(move |&i| pile[i].for_sale).map(move |i| (p, i))
+    }).collect()
+}
+
+fn buy_menu(game: &Game) -> Option<Box<dyn State>> {
+    let stock = stock(game);
+    if stock.is_empty() {
+        return Some(box MenuState::new("\"I'm afraid I've nothing left to sell\"", vec!["Leave".to_string()], box |_, _| {
+            None
+        }))
+    }
+
+    let items = stock.iter().map(|&(p, i)| game.level.items[&p][i].describe_to(&game.level.discoveries)).collect();
+
+    Some(box ItemSelectState::new("Buy what?", items, box move |game, picked| {
+        let chosen = picked.into_iter().map(|i| stock[i]).collect::<Vec<_>>();
+        buy(game, chosen);
+    }))
+}
+
+/// Buys the items at the given places on the shop floor, if the player can afford and carry them all
+fn buy(game: &mut Game, chosen: Vec<(Point, usize)>) {
+    let cost = chosen.iter().map(|&(p, i)| game.level.items[&p][i].price()).sum::<u32>();
+    let weight = chosen.iter().map(|&(p, i)| game.level.items[&p][i].weight()).sum::<u32>();
+
+    let player = game.level.entities[0].as_ref().unwrap();
+    if cost > player.gold {
+        game.game_log(format!("\"That comes to {} gold, and you only have {}\"", cost, player.gold));
+        return
+    }
+    if weight > player.spare_capacity() {
+        game.game_log("You can't carry all that".to_string());
+        return
+    }
+
+    let mut bought = take_from_floor(game, chosen);
+    bought.iter_mut().for_each(|item| item.claim(false));
+    let names = bought.iter().map(|item| item.describe_to(&game.level.discoveries)).collect::<Vec<_>>();
+
+    let player = game.level.entities[0].as_mut().unwrap();
+    player.gold -= cost;
+    for item in bought {
+        player.stow(item);
+    }
+    game.game_log(format!("You buy {} for {} gold", names.join(", "), cost));
+}
+
+/// Lifts the items at the given places off the floor
+fn take_from_floor(game: &mut Game, mut chosen: Vec<(Point, usize)>) -> Vec<Item> {
+    // Taken from the back of each pile so the earlier places in it stay put
+    chosen.sort();
+    let mut taken = vec![];
+    for (point, i) in chosen.into_iter().rev() {
+        let pile = game.level.items.get_mut(&point).unwrap();
+        taken.push(pile.remove(i));
+        if pile.is_empty() {
+            game.level.items.remove(&point);
+        }
+    }
+
+    taken.reverse();
+    taken
+}
+
+/// What the shopkeeper will pay for `item`
+fn offer(item: &Item) -> u32 {
+    item.price() / SELL_DIVISOR
+}
+
+fn sell_menu(game: &Game) -> Option<Box<dyn State>> {
+    let player = game.level.entities[0].as_ref().unwrap();
+    let sellable = (0..player.inventory.len()).filter(|&i| !player.inventory[i].unpaid && !player.inventory[i].worn).collect::<Vec<_>>();
+    if sellable.is_empty() {
+        return Some(box MenuState::new("\"You've nothing I'd buy\"", vec!["Leave".to_string()], box |_, _| {
+            None
+        }))
+    }
+
+    let items = sellable.iter().map(|&i| {
+        let item = &player.inventory[i];
+        format!("{} ({} gold)", item.describe_to(&game.level.discoveries), offer(item))
+    }).collect();
+
+    Some(box ItemSelectState::new("Sell what?", items, box move |game, picked| {
+        let chosen = picked.into_iter().map(|i| sellable[i]).collect::<Vec<_>>();
+        sell(game, chosen);
+    }))
+}
+
+/// Sells the items at the given places in the player's pack, which the shopkeeper puts down where they stand
+fn sell(game: &mut Game, chosen: Vec<usize>) {
+    let keeper = match game.level.shopkeeper() {
+        Some(keeper) => game.level.entities[keeper].as_ref().unwrap().get_position(),
+        None => return,
+    };
+
+    let player = game.level.entities[0].as_mut().unwrap();
+    let mut sold = vec![];
+    for i in chosen.into_iter().rev() {
+        sold.insert(0, player.inventory.remove(i));
+    }
+
+    let earned = sold.iter().map(offer).sum::<u32>();
+    player.gold += earned;
+
+    let names = sold.iter().map(|item| item.describe_to(&game.level.discoveries)).collect::<Vec<_>>();
+    for mut item in sold {
+        item.position = keeper;
+        item.put_up_for_sale();
+        item::add_to_pile(game.level.items.entry(keeper).or_insert_with(Vec::new), item);
+    }
+    game.game_log(format!("You sell {} for {} gold", names.join(", "), earned));
+}
+
+/// Pays for everything the player has picked up in the shop, if they have the gold
+fn pay(game: &mut Game) {
+    let player = game.level.entities[0].as_mut().unwrap();
+    let bill = player.bill();
+    if bill > player.gold {
+        let message = format!("\"You owe me {} gold, and you only have {}\"", bill, player.gold);
+        game.game_log(message);
+        return
+    }
+
+    player.gold -= bill;
+    player.clear_unpaid();
+    game.game_log(format!("You pay {} gold. \"Thank you for shopping!\"", bill));
+}
@@ -1,8 +1,40 @@
-use crate::game::{Game, MAP_WIDTH};
+use crate::game::{Game, MAP_OFFSET};
 use crate::Exit;
-use crate::util::add_punctuation;
+use crate::util::{add_punctuation, Point};
+use crate::keymap::Command;
+use crate::action::Direction;
+use crate::ui::{self, Menu, Pager};
+use crate::wizard;
+use crate::console;
+use crate::shop;
+use crate::container;
+use crate::morgue;
+use crate::scores::{self, Score, TOP_SCORES};
+use crate::save::{self, Slot, SaveData};
+use crate::actor::Actor;
+use crate::map::Generator;
+use crate::item::ItemType;
 
-use tcod::input::KeyCode;
+use tcod::Color;
+use tcod::input::{Key, KeyCode};
+
+use std::path::PathBuf;
+
+/// What to do with the option picked from a menu. May return a state to carry on to, such as another menu.
+pub type MenuCallback = Box<dyn FnOnce(&mut Game, usize) -> Option<Box<dyn State>>>;
+/// What to do with the tile picked by a `TargetState`
+pub type TargetCallback = Box<dyn FnOnce(&mut Game, Point)>;
+/// What to do with the options picked from an `ItemSelectState`, by their place in the list
+pub type ItemSelectCallback = Box<dyn FnOnce(&mut Game, Vec<usize>)>;
+/// What to do with the line typed into a `TextInputState`
+pub type TextInputCallback = Box<dyn FnOnce(&mut Game, String)>;
+/// What to do with the options picked from each side of a `TwoPaneSelectState`, by their place in its list
+pub type TwoPaneCallback = Box<dyn FnOnce(&mut Game, Vec<usize>, Vec<usize>)>;
+/// What to do with the direction picked from a `DirectionState`
+pub type DirectionCallback = Box<dyn FnOnce(&mut Game, Direction)>;
+
+/// The longest name the player can give a kind of item
+const MAX_CALLED_NAME: usize = 30;
 
 pub enum PlayerState {
     Play,
@@ -30,9 +62,45 @@ pub trait State {
     fn set_game(&mut self, game: Game);
 }
 
+/// Runs the game loop over `states` until the game exits, returning how it did.
+/// If `menu_at_bottom`, the first state is the main menu, and closing the window there leaves the save alone.
+/// Closing it anywhere else is an `Exit::Close`.
+pub fn run(states: &mut Vec<Box<dyn State>>, menu_at_bottom: bool) -> Exit {
+    states[0].render();
+    loop {
+        let mut i = states.len() - 1;
+
+        if states[i].get_game().rendering_component.window_closed() || states[i].get_game().exit {
+            return if menu_at_bottom && states.len() == 1 { Exit::Quit } else { Exit::Close }
+        }
+
+        // Update state
+        if states[i].should_exit() {
+            let game = states.pop().unwrap().exit();
+            i -= 1;
+            states[i].set_game(game);
+        } else {
+            if let Some(new_state) = states[i].maybe_new_state() {
+                states.push(new_state);
+                i += 1;
+            }
+        }
+
+        states[i].update();
+        states[i].render();
+
+        // Check if the game should exit
+        if let Some(exit) = states[i].maybe_exit_game() {
+            return exit
+        }
+    }
+}
+
 pub struct PlayState {
     game: Option<Game>,
     should_exit: Option<Exit>,
+    /// A state the player asked for, such as the wizard menu
+    next: Option<Box<dyn State>>,
 }
 
 pub struct MessageState {
@@ -40,18 +108,154 @@ pub struct MessageState {
     should_exit: Option<Exit>,
 }
 
+/// A menu of items drawn over the map, any number of which can be picked.
+/// Letters pick and unpick items, comma picks them all, Enter takes the picked
+/// ones and Escape closes it without taking anything.
 pub struct ItemSelectState {
+    game: Option<Game>,
+    title: String,
+    items: Vec<String>,
+    picked: Vec<bool>,
+    pager: Pager,
+    menu: Menu,
+    on_select: Option<ItemSelectCallback>,
+    done: bool,
+}
+
+/// Two lists of items drawn side by side over the map, such as what's in a chest
+/// and what's in the pack, any number of which can be picked from each. Tab
+/// switches sides, and letters and comma pick items on the side being picked
+/// from like an `ItemSelectState`. Enter takes the picked ones and Escape
+/// closes it without taking anything.
+pub struct TwoPaneSelectState {
+    game: Option<Game>,
+    panes: [Pane; 2],
+    /// Which side is being picked from
+    active: usize,
+    on_select: Option<TwoPaneCallback>,
+    done: bool,
+}
+
+/// One side of a `TwoPaneSelectState`
+struct Pane {
+    title: String,
+    items: Vec<String>,
+    picked: Vec<bool>,
+    pager: Pager,
+}
+
+/// A menu drawn over the map, whose options are picked by their letter.
+/// Escape closes it without picking anything.
+pub struct MenuState {
+    game: Option<Game>,
+    title: String,
+    options: Vec<String>,
+    pager: Pager,
+    menu: Menu,
+    on_select: Option<MenuCallback>,
+    /// The state the picked option led to, if any
+    next: Option<Box<dyn State>>,
+    done: bool,
+}
+
+/// Asks the player to type a line over the map, such as a name.
+/// Enter takes what was typed and Escape cancels.
+pub struct TextInputState {
+    game: Option<Game>,
+    prompt: String,
+    input: String,
+    max_len: usize,
+    on_submit: Option<TextInputCallback>,
+    done: bool,
+}
+
+/// Asks the player which way to do something, such as kick, taking any
+/// movement key. Escape cancels.
+pub struct DirectionState {
+    game: Option<Game>,
+    prompt: String,
+    on_direction: Option<DirectionCallback>,
+    done: bool,
+}
+
+/// Lets the player pick a tile by moving a cursor around the map
+pub struct TargetState {
+    game: Option<Game>,
+    prompt: String,
+    cursor: Option<Point>,
+    on_target: Option<TargetCallback>,
+    done: bool,
+}
+
+/// The game over screen, recapping how the game went. Any of Enter,
+/// Escape or Space ends the game.
+pub struct DeathState {
+    game: Option<Game>,
+    /// The high score table, once the recap has been read
+    next: Option<Box<dyn State>>,
+}
+
+/// The high score table. After a game it highlights the score just made,
+/// and closing it ends the game.
+pub struct ScoresState {
     game: Option<Game>,
     should_exit: Option<Exit>,
+    lines: Vec<String>,
+    /// Whether closing the table ends the game, rather than going back to the main menu
+    ends_game: bool,
+    done: bool,
 }
 
-// For when I eventually add a splash screen
-#[allow(dead_code)]
-pub struct SplashState;
+/// The title screen and main menu, shown before a game starts.
+/// It holds a game to draw with, which is restarted when one is picked.
+pub struct SplashState {
+    game: Option<Game>,
+    should_exit: Option<Exit>,
+    /// The saved games that can be continued
+    saves: Vec<(Slot, SaveData)>,
+    /// The autosaves of games that didn't exit cleanly, which can be resumed
+    interrupted: Vec<(Slot, SaveData)>,
+    /// What went wrong looking for saves, shown under the menu
+    problems: Vec<String>,
+    /// The save file given on the command line, which new games go in instead of a named slot
+    save_file: Option<PathBuf>,
+    menu: Menu,
+    /// The title and names of the options showing, kept for turning the menu's page
+    title: String,
+    names: Vec<String>,
+    pager: Pager,
+    options: Vec<MainMenuOption>,
+    /// The name being typed for a new game's save slot, and the name used if it's left blank
+    naming: Option<(String, String)>,
+    /// Whether a game has been picked and is ready to play
+    start: bool,
+    /// The state an option led to, such as the high scores
+    next: Option<Box<dyn State>>,
+}
+
+/// What the main menu and its pages offer
+#[derive(Clone, Copy, PartialEq)]
+enum MainMenuOption {
+    /// Resume the interrupted game at this index from its autosave
+    Resume(usize),
+    NewGame,
+    Continue,
+    /// Continue the save at this index
+    Save(usize),
+    HighScores,
+    Options,
+    Quit,
+    /// Cycles through the map generators
+    Generator,
+    /// Turns wizard mode on or off for the new game
+    Wizard,
+    /// Back from a page to the main menu
+    Back,
+}
 
 impl PlayState {
     pub fn new(game: Game) -> PlayState {
-        PlayState { game: Some(game), should_exit: None, }
+        PlayState { game: Some(game), should_exit: None, next: None, }
     }
 }
 
@@ -61,7 +265,9 @@ impl State for PlayState {
             return Some(box MessageState::new(self.game.take().unwrap()))
         }
 
-        None
+        let mut next = self.next.take()?;
+        next.set_game(self.game.take().unwrap());
+        Some(next)
     }
     fn maybe_exit_game(&self) -> Option<Exit> { self.should_exit }
     fn should_exit(&self) -> bool { false }
@@ -69,19 +275,493 @@ impl State for PlayState {
     fn exit(&mut self) -> Game { self.game.take().unwrap() }
 
     fn update(&mut self) {
-        let keypress = self.game.as_mut().unwrap().wait_for_keypress();
-        match keypress.code {
-            KeyCode::Escape => if keypress.shift {
+        // Let any messages be shown before asking for the next key
+        if !self.game.as_ref().unwrap().message_queue.is_empty() {
+            return
+        }
+
+        // What to pick up or use was chosen from a menu, so the turn goes ahead without another key
+        let level = &self.game.as_ref().unwrap().level;
+        let chosen = level.pickup_choice.is_some() || level.use_choice.is_some() || level.open_choice.is_some() || level.kick_choice.is_some();
+        if !chosen {
+            self.game.as_mut().unwrap().wait_for_keypress();
+        }
+        match self.game.as_ref().unwrap().level.command {
+            _ if chosen => {}
+            Some(Command::SaveAndQuit) => {
                 self.should_exit = Some(Exit::Save);
                 return
-            } else {
-                self.should_exit = Some(Exit::Die);
+            }
+            Some(Command::Quit) => {
+                self.next = Some(box DeathState::new());
+                return
+            }
+            Some(Command::Wizard) => {
+                self.next = Some(wizard::open(self.game.as_ref().unwrap()));
+                return
+            }
+            Some(Command::Console) => {
+                self.next = Some(wizard::with_wizard(self.game.as_ref().unwrap(), console::open));
+                return
+            }
+            Some(Command::Inventory) => {
+                self.next = inventory_menu(self.game.as_ref().unwrap());
+                if self.next.is_none() {
+                    self.game.as_mut().unwrap().game_log("You aren't carrying anything".to_string());
+                }
+                return
+            }
+            Some(Command::Use) => {
+                self.next = use_menu(self.game.as_ref().unwrap());
+                if self.next.is_none() {
+                    self.game.as_mut().unwrap().game_log("You have nothing you can use".to_string());
+                }
+                return
+            }
+            Some(Command::Open) => {
+                self.next = container::open_menu(self.game.as_mut().unwrap());
+                return
+            }
+            Some(Command::Kick) => {
+                self.next = Some(box DirectionState::new("Kick in which direction?", box |game, direction| {
+                    game.level.kick_choice = Some(direction);
+                    game.level.command = Some(Command::Kick);
+                }));
+                return
+            }
+            Some(Command::PickUp) => if let Some(menu) = pickup_menu(self.game.as_ref().unwrap()) {
+                self.next = Some(menu);
                 return
             }
             _ => {}
         }
 
+        // A headless replay has run out of keys
+        if self.game.as_ref().unwrap().exit {
+            return
+        }
+
         self.game.as_mut().unwrap().update();
+
+        if self.game.as_ref().unwrap().level.entities[0].as_ref().unwrap().is_dead() {
+            self.next = Some(box DeathState::new());
+        } else if self.game.as_ref().unwrap().level.trading {
+            self.game.as_mut().unwrap().level.trading = false;
+            self.next = Some(shop::menu(self.game.as_ref().unwrap()));
+        }
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
+
+impl DeathState {
+    pub fn new() -> DeathState {
+        DeathState { game: None, next: None }
+    }
+}
+
+impl State for DeathState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> {
+        let mut next = self.next.take()?;
+        next.set_game(self.game.take().unwrap());
+        Some(next)
+    }
+    fn maybe_exit_game(&self) -> Option<Exit> { None }
+    fn should_exit(&self) -> bool { false }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        let keypress = game.wait_for_keypress();
+        match keypress.code {
+            KeyCode::Enter | KeyCode::NumPadEnter | KeyCode::Escape | KeyCode::Spacebar => {
+                let score = Score::new(game);
+
+                // Replays have already been scored when they were played
+                let problem = if game.replaying {
+                    None
+                } else {
+                    scores::record(&score).err().map(|e| format!("Could not save your score: {}", e.to_string()))
+                };
+
+                self.next = Some(box ScoresState::new(Some(score), problem, true));
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        game.rendering_component.before_render_new_frame();
+
+        let mut lines = vec!["GAME OVER".to_string(), String::new()];
+        lines.extend(morgue::summary(game));
+        lines.push(String::new());
+        lines.push("Press Enter to continue".to_string());
+
+        let top = (game.window_bounds.max.y - lines.len() as i32) / 3;
+        for (i, line) in lines.iter().enumerate() {
+            game.rendering_component.print(line, 4, top + i as i32);
+        }
+
+        game.rendering_component.after_render_new_frame();
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
+
+impl ScoresState {
+    /// Creates the high score table, highlighting `latest` if it made the
+    /// top scores. `problem` is shown if the score couldn't be recorded.
+    pub fn new(latest: Option<Score>, problem: Option<String>, ends_game: bool) -> ScoresState {
+        let mut lines = vec!["High Scores".to_string(), String::new()];
+
+        match scores::load() {
+            Ok(all) => {
+                let top = scores::top(&all, TOP_SCORES);
+                if top.is_empty() {
+                    lines.push("No scores yet".to_string());
+                }
+
+                for (i, score) in top.iter().enumerate() {
+                    let marker = if latest.as_ref() == Some(score) { ">" } else { " " };
+                    lines.push(format!("{} {:>2}. {}", marker, i + 1, score.describe()));
+                }
+
+                if let Some(score) = &latest {
+                    lines.push(String::new());
+                    if score.wizard {
+                        lines.push("Wizard games don't count for the high score".to_string());
+                    } else if !top.contains(score) {
+                        lines.push(format!("Your score:  {}", score.describe()));
+                    }
+                }
+            }
+            Err(e) => lines.push(format!("Could not read the high scores: {}", e.to_string())),
+        }
+
+        if let Some(problem) = problem {
+            lines.push(problem);
+        }
+
+        ScoresState { game: None, should_exit: None, lines, ends_game, done: false }
+    }
+}
+
+impl State for ScoresState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> { None }
+    fn maybe_exit_game(&self) -> Option<Exit> { self.should_exit }
+    fn should_exit(&self) -> bool { self.done }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        let keypress = self.game.as_mut().unwrap().wait_for_keypress();
+        match keypress.code {
+            KeyCode::Enter | KeyCode::NumPadEnter | KeyCode::Escape | KeyCode::Spacebar => {
+                if self.ends_game {
+                    self.should_exit = Some(Exit::Die);
+                } else {
+                    self.done = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        game.rendering_component.before_render_new_frame();
+
+        let top = (game.window_bounds.max.y - self.lines.len() as i32) / 3;
+        for (i, line) in self.lines.iter().enumerate() {
+            game.rendering_component.print(line, 2, top + i as i32);
+        }
+
+        game.rendering_component.after_render_new_frame();
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
+
+/// The longest name a save slot can be given
+const MAX_SLOT_NAME: usize = 24;
+
+impl SplashState {
+    /// Creates the main menu over `game`, offering to continue any of `slots` and to resume
+    /// any `interrupted` slot from its autosave. New games are saved to `save_file` if there
+    /// is one, else to a slot the player names.
+    pub fn new(game: Game, slots: Result<Vec<Slot>, String>, interrupted: Vec<Slot>, save_file: Option<PathBuf>) -> SplashState {
+        let mut saves = vec![];
+        let mut problems = vec![];
+        match slots {
+            Ok(slots) => for slot in slots {
+                match slot.load() {
+                    Ok(data) => saves.push((slot, data)),
+                    Err(e) => problems.push(format!("The save '{}' can't be continued: {}", slot.name, e)),
+                }
+            }
+            Err(e) => problems.push(e),
+        }
+
+        let interrupted = interrupted.into_iter().filter_map(|slot| match slot.load_autosave() {
+            Ok(data) => Some((slot, data)),
+            Err(e) => {
+                problems.push(format!("The autosave of '{}' can't be resumed: {}", slot.name, e));
+                None
+            }
+        }).collect();
+
+        let mut splash = SplashState {
+            game: Some(game),
+            should_exit: None,
+            saves,
+            interrupted,
+            problems,
+            save_file,
+            menu: ui::menu("", vec![String::new()]).unwrap(),
+            title: String::new(),
+            names: vec![],
+            pager: Pager::new(0),
+            options: vec![],
+            naming: None,
+            start: false,
+            next: None,
+        };
+
+        splash.show_main_menu();
+        splash
+    }
+
+    fn show_main_menu(&mut self) {
+        let mut options = (0..self.interrupted.len()).map(MainMenuOption::Resume).collect::<Vec<_>>();
+        options.push(MainMenuOption::NewGame);
+        if !self.saves.is_empty() {
+            options.push(MainMenuOption::Continue);
+        }
+        options.push(MainMenuOption::HighScores);
+        options.push(MainMenuOption::Options);
+        options.push(MainMenuOption::Quit);
+
+        self.show("Main Menu", options);
+    }
+
+    fn show_saves(&mut self) {
+        let mut options = (0..self.saves.len()).map(MainMenuOption::Save).collect::<Vec<_>>();
+        options.push(MainMenuOption::Back);
+
+        self.show("Continue which game?", options);
+    }
+
+    fn show_options(&mut self) {
+        self.show("Options", vec![MainMenuOption::Generator, MainMenuOption::Wizard, MainMenuOption::Back]);
+    }
+
+    fn show(&mut self, title: &str, options: Vec<MainMenuOption>) {
+        let settings = self.game.as_ref().unwrap().settings;
+        let names = options.iter().map(|o| match o {
+            MainMenuOption::Resume(i) => {
+                let (slot, data) = &self.interrupted[*i];
+                format!("Resume {} from turn {} (the game didn't exit cleanly)", slot.name, data.turns)
+            }
+            MainMenuOption::NewGame => "New Game".to_string(),
+            MainMenuOption::Continue => match self.saves.as_slice() {
+                [(slot, data)] => format!("Continue {} (seed {})", slot.name, data.seed),
+                saves => format!("Continue ({} saved games)", saves.len()),
+            }
+            MainMenuOption::Save(i) => {
+                let (slot, data) = &self.saves[*i];
+                format!("{} (seed {}){}", slot.name, data.seed, if data.wizard { " (wizard)" } else { "" })
+            }
+            MainMenuOption::HighScores => "High Scores".to_string(),
+            MainMenuOption::Options => "Options".to_string(),
+            MainMenuOption::Quit => "Quit".to_string(),
+            MainMenuOption::Generator => format!("Map generator: {}", settings.generator.name()),
+            MainMenuOption::Wizard => format!("Wizard mode: {}", if settings.wizard { "on" } else { "off" }),
+            MainMenuOption::Back => "Back".to_string(),
+        }).collect::<Vec<_>>();
+
+        self.pager = Pager::new(names.len());
+        self.menu = self.pager.menu(title, &names).unwrap();
+        self.title = title.to_string();
+        self.names = names;
+        self.options = options;
+    }
+
+    /// Starts the game saved in `slot`, or a new one if there's nothing saved.
+    /// Saves with a replay pick up where they left off; older ones start over from their seed.
+    /// Either way, the save has to have been made from the same content as this build makes.
+    fn start(&mut self, slot: Option<Slot>, save: Option<SaveData>) {
+        let game = self.game.as_mut().unwrap();
+
+        if let Some(Err(e)) = save.as_ref().map(|s| s.check_content()) {
+            let name = slot.map_or(String::new(), |s| s.name);
+            self.problems = vec![format!("The game '{}' can't be resumed: {}", name, e)];
+            return
+        }
+
+        match save {
+            Some(SaveData { replay: Some(replay), .. }) => if let Err(e) = game.resume(replay) {
+                let name = slot.map_or(String::new(), |s| s.name);
+                self.problems = vec![format!("The game '{}' can't be resumed: {}", name, e)];
+                return
+            }
+            save => {
+                // Once a wizard, always a wizard
+                game.settings.wizard |= save.as_ref().map_or(false, |s| s.wizard);
+                game.restart(save.map(|s| s.seed));
+            }
+        }
+        game.save_slot = slot;
+
+        self.start = true;
+    }
+
+    /// Types the name of a new game's save slot. Enter starts the game, and Escape goes back.
+    fn update_naming(&mut self, keypress: Key) {
+        let (mut input, default) = self.naming.take().unwrap();
+        match keypress.code {
+            KeyCode::Escape => return,
+            KeyCode::Enter | KeyCode::NumPadEnter => {
+                let name = if input.trim().is_empty() { default.clone() } else { input.trim().to_string() };
+                match Slot::named(&name) {
+                    Some(ref slot) if slot.exists() => self.problems = vec![format!("There's already a save called '{}'", name)],
+                    slot => {
+                        if slot.is_none() {
+                            self.problems = vec!["Could not find a config directory to save in".to_string()];
+                        }
+                        self.start(slot, None);
+                        return
+                    }
+                }
+            }
+            KeyCode::Backspace => { input.pop(); }
+            _ => if keypress.printable != '\0' && !keypress.printable.is_control() && input.chars().count() < MAX_SLOT_NAME {
+                input.push(keypress.printable);
+            }
+        }
+
+        self.naming = Some((input, default));
+    }
+}
+
+impl State for SplashState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> {
+        if self.start {
+            self.start = false;
+            return Some(box PlayState::new(self.game.take().unwrap()))
+        }
+
+        let mut next = self.next.take()?;
+        next.set_game(self.game.take().unwrap());
+        Some(next)
+    }
+    fn maybe_exit_game(&self) -> Option<Exit> { self.should_exit }
+    fn should_exit(&self) -> bool { false }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        let keypress = self.game.as_mut().unwrap().wait_for_keypress();
+        if self.naming.is_some() {
+            self.update_naming(keypress);
+            return
+        }
+
+        let choice = match keypress.code {
+            KeyCode::Escape => MainMenuOption::Back,
+            KeyCode::Char if self.pager.turn(keypress.printable) => {
+                self.menu = self.pager.menu(&self.title, &self.names).unwrap();
+                return
+            }
+            KeyCode::Char => match self.pager.pick(keypress.printable) {
+                Some(i) => self.options[i],
+                None => return,
+            }
+            _ => return,
+        };
+
+        match choice {
+            MainMenuOption::NewGame => match self.save_file.clone() {
+                Some(path) => self.start(Some(Slot::at(&path)), None),
+                None => self.naming = Some((String::new(), save::unused_name())),
+            }
+            MainMenuOption::Continue => if self.saves.len() == 1 {
+                let (slot, data) = self.saves[0].clone();
+                self.start(Some(slot), Some(data));
+            } else {
+                self.show_saves();
+            }
+            MainMenuOption::Save(i) => {
+                let (slot, data) = self.saves[i].clone();
+                self.start(Some(slot), Some(data));
+            }
+            MainMenuOption::Resume(i) => {
+                let (slot, data) = self.interrupted[i].clone();
+                self.start(Some(slot), Some(data));
+            }
+            MainMenuOption::HighScores => self.next = Some(box ScoresState::new(None, None, false)),
+            MainMenuOption::Options => self.show_options(),
+            MainMenuOption::Quit => self.should_exit = Some(Exit::Quit),
+            MainMenuOption::Generator => {
+                let game = self.game.as_mut().unwrap();
+                let all = Generator::ALL;
+                let i = all.iter().position(|&g| g == game.settings.generator).unwrap();
+                game.settings.generator = all[(i + 1) % all.len()];
+                self.show_options();
+            }
+            MainMenuOption::Wizard => {
+                let game = self.game.as_mut().unwrap();
+                game.settings.wizard = !game.settings.wizard;
+                self.show_options();
+            }
+            MainMenuOption::Back => {
+                // Escape on the main menu itself quits
+                if self.options.contains(&MainMenuOption::Quit) {
+                    self.should_exit = Some(Exit::Quit);
+                } else {
+                    self.show_main_menu();
+                }
+            }
+        }
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        game.rendering_component.before_render_new_frame();
+
+        let width = game.window_bounds.max.x;
+        let title = "MR: TOM".to_string();
+        let top = (game.window_bounds.max.y - self.menu.height() as i32) / 3;
+        game.rendering_component.print(&title, (width - title.len() as i32) / 2, top);
+
+        let mut lines = match &self.naming {
+            Some((input, default)) => vec![
+                format!("Name the new game's save (Enter for '{}'):", default),
+                String::new(),
+                format!("> {}_", input),
+            ],
+            None => self.menu.lines().to_vec(),
+        };
+        lines.push(String::new());
+        lines.extend(self.problems.iter().cloned());
+
+        let x = (width - self.menu.width() as i32) / 2;
+        for (i, line) in lines.iter().enumerate() {
+            game.rendering_component.print(line, x, top + 3 + i as i32);
+        }
+
+        game.rendering_component.after_render_new_frame();
     }
 
     fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
@@ -109,9 +789,10 @@ impl State for MessageState {
 
         if !self.game.as_mut().unwrap().message_queue.is_empty() {
             let game = self.game.as_mut().unwrap();
+            let width = game.window_bounds.max.x;
 
             let mut message = game.message_queue.drain(..).map(|s| add_punctuation(s)).collect::<Vec<String>>().join(" ");
-            if message.len() > (MAP_WIDTH - 21) as usize {
+            if message.len() > (width - 21) as usize {
                 let spaces = message.match_indices(" ").collect::<Vec<_>>();
                 let m_clone = message.clone();
                 let (first, last) = m_clone.split_at(spaces[spaces.len()/2].0);
@@ -121,7 +802,7 @@ impl State for MessageState {
             game.rendering_component.push_message(&message);
             game.message_cache.push(message);
             if !game.message_queue.is_empty() {
-                game.rendering_component.print(&"-- enter for more --".to_string(), MAP_WIDTH - 21, 0);
+                game.rendering_component.print(&"-- enter for more --".to_string(), width - 21, 0);
             }
         }
 
@@ -143,4 +824,468 @@ impl State for MessageState {
     fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
 
     fn set_game(&mut self, game: Game) { self.game = Some(game) }
-}
\ No newline at end of file
+}
+impl MenuState {
+    /// Creates a menu of `options`. Like every state but the `PlayState`,
+    /// it's handed the game when it's pushed.
+    pub fn new(title: &str, options: Vec<String>, on_select: MenuCallback) -> MenuState {
+        let pager = Pager::new(options.len());
+        MenuState {
+            game: None,
+            menu: pager.menu(title, &options).expect("a menu needs options"),
+            title: title.to_string(),
+            options,
+            pager,
+            on_select: Some(on_select),
+            next: None,
+            done: false,
+        }
+    }
+}
+
+impl State for MenuState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> {
+        let mut next = self.next.take()?;
+        next.set_game(self.game.take().unwrap());
+        Some(next)
+    }
+    fn maybe_exit_game(&self) -> Option<Exit> { None }
+    fn should_exit(&self) -> bool { self.done && self.next.is_none() }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        // Back from whatever the picked option led to, and about to close
+        if self.done {
+            return
+        }
+
+        let keypress = self.game.as_mut().unwrap().wait_for_keypress();
+        match keypress.code {
+            KeyCode::Escape => self.done = true,
+            KeyCode::Char => if self.pager.turn(keypress.printable) {
+                self.menu = self.pager.menu(&self.title, &self.options).unwrap();
+            } else if let Some(choice) = self.pager.pick(keypress.printable) {
+                let on_select = self.on_select.take().unwrap();
+                self.next = on_select(self.game.as_mut().unwrap(), choice);
+                self.done = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        game.rendering_component.before_render_new_frame();
+        game.render();
+
+        // Centre the menu over the map
+        let x = (game.window_bounds.max.x - self.menu.width() as i32) / 2;
+        let y = (game.window_bounds.max.y - self.menu.height() as i32) / 2;
+        for (i, line) in self.menu.lines().iter().enumerate() {
+            game.rendering_component.print(line, x, y + i as i32);
+        }
+
+        game.rendering_component.after_render_new_frame();
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
+
+impl ItemSelectState {
+    /// Creates a menu of `items`. Like every state but the `PlayState`,
+    /// it's handed the game when it's pushed.
+    pub fn new(title: &str, items: Vec<String>, on_select: ItemSelectCallback) -> ItemSelectState {
+        let picked = vec![false; items.len()];
+        let pager = Pager::new(items.len());
+        let menu = Self::build_menu(title, &items, &picked, pager);
+        ItemSelectState { game: None, title: title.to_string(), items, picked, pager, menu, on_select: Some(on_select), done: false }
+    }
+
+    fn build_menu(title: &str, items: &[String], picked: &[bool], pager: Pager) -> Menu {
+        let options = items.iter().zip(picked).map(|(item, &picked)| format!("{} {}", if picked { "+" } else { "-" }, item)).collect::<Vec<_>>();
+        pager.menu(title, &options).expect("an item menu needs items")
+    }
+}
+
+impl State for ItemSelectState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> { None }
+    fn maybe_exit_game(&self) -> Option<Exit> { None }
+    fn should_exit(&self) -> bool { self.done }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        let keypress = self.game.as_mut().unwrap().wait_for_keypress();
+        match keypress.code {
+            KeyCode::Escape => self.done = true,
+            KeyCode::Enter | KeyCode::NumPadEnter => {
+                let picked = (0..self.items.len()).filter(|&i| self.picked[i]).collect::<Vec<_>>();
+                if !picked.is_empty() {
+                    let on_select = self.on_select.take().unwrap();
+                    on_select(self.game.as_mut().unwrap(), picked);
+                }
+                self.done = true;
+            }
+            KeyCode::Char if keypress.printable == ',' => {
+                let all = self.picked.iter().all(|&p| p);
+                self.picked.iter_mut().for_each(|p| *p = !all);
+            }
+            KeyCode::Char => if !self.pager.turn(keypress.printable) {
+                if let Some(choice) = self.pager.pick(keypress.printable) {
+                    self.picked[choice] = !self.picked[choice];
+                }
+            }
+            _ => return,
+        }
+
+        self.menu = Self::build_menu(&self.title, &self.items, &self.picked, self.pager);
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        game.rendering_component.before_render_new_frame();
+        game.render();
+
+        // Centre the menu over the map
+        let x = (game.window_bounds.max.x - self.menu.width() as i32) / 2;
+        let y = (game.window_bounds.max.y - self.menu.height() as i32) / 2;
+        for (i, line) in self.menu.lines().iter().enumerate() {
+            game.rendering_component.print(line, x, y + i as i32);
+        }
+
+        game.rendering_component.after_render_new_frame();
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
+
+impl TwoPaneSelectState {
+    /// Creates a pair of lists, the left one picked from first. Like every state but
+    /// the `PlayState`, it's handed the game when it's pushed.
+    pub fn new(left_title: &str, left: Vec<String>, right_title: &str, right: Vec<String>, on_select: TwoPaneCallback) -> TwoPaneSelectState {
+        TwoPaneSelectState {
+            game: None,
+            panes: [Pane::new(left_title, left), Pane::new(right_title, right)],
+            active: 0,
+            on_select: Some(on_select),
+            done: false,
+        }
+    }
+}
+
+impl Pane {
+    fn new(title: &str, items: Vec<String>) -> Pane {
+        let picked = vec![false; items.len()];
+        let pager = Pager::new(items.len());
+        Pane { title: title.to_string(), items, picked, pager }
+    }
+
+    fn picked(&self) -> Vec<usize> {
+        (0..self.items.len()).filter(|&i| self.picked[i]).collect()
+    }
+
+    /// The side drawn as a menu, its title marked if it's the side being picked from
+    fn menu(&self, active: bool) -> Menu {
+        let title = format!("{}{}", if active { "> " } else { "  " }, self.title);
+        let options = self.items.iter().zip(&self.picked).map(|(item, &picked)| format!("{} {}", if picked { "+" } else { "-" }, item)).collect::<Vec<_>>();
+        self.pager.menu(&title, &options).unwrap_or_else(|| ui::text_box(&title, vec!["nothing".to_string()]))
+    }
+}
+
+impl State for TwoPaneSelectState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> { None }
+    fn maybe_exit_game(&self) -> Option<Exit> { None }
+    fn should_exit(&self) -> bool { self.done }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        let keypress = self.game.as_mut().unwrap().wait_for_keypress();
+        let pane = &mut self.panes[self.active];
+        match keypress.code {
+            KeyCode::Escape => self.done = true,
+            KeyCode::Tab => self.active = 1 - self.active,
+            KeyCode::Enter | KeyCode::NumPadEnter => {
+                let (left, right) = (self.panes[0].picked(), self.panes[1].picked());
+                if !left.is_empty() || !right.is_empty() {
+                    let on_select = self.on_select.take().unwrap();
+                    on_select(self.game.as_mut().unwrap(), left, right);
+                }
+                self.done = true;
+            }
+            KeyCode::Char if keypress.printable == ',' => {
+                let all = pane.picked.iter().all(|&p| p);
+                pane.picked.iter_mut().for_each(|p| *p = !all);
+            }
+            KeyCode::Char => if !pane.pager.turn(keypress.printable) {
+                if let Some(choice) = pane.pager.pick(keypress.printable) {
+                    pane.picked[choice] = !pane.picked[choice];
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        game.rendering_component.before_render_new_frame();
+        game.render();
+
+        // Centre the pair over the map, a space apart
+        let menus = [self.panes[0].menu(self.active == 0), self.panes[1].menu(self.active == 1)];
+        let width = menus[0].width() as i32 + 1 + menus[1].width() as i32;
+        let height = menus[0].height().max(menus[1].height()) as i32;
+        let mut x = (game.window_bounds.max.x - width) / 2;
+        let y = (game.window_bounds.max.y - height) / 2;
+        for menu in &menus {
+            for (i, line) in menu.lines().iter().enumerate() {
+                game.rendering_component.print(line, x, y + i as i32);
+            }
+            x += menu.width() as i32 + 1;
+        }
+
+        game.rendering_component.print(&"Tab switches sides, Enter moves what's picked".to_string(), 0, MAP_OFFSET - 1);
+        game.rendering_component.after_render_new_frame();
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
+
+/// The menu for choosing what to take from the pile under the player, if there's more than one
+/// kind of thing in it. Picking up takes the player's turn once the menu closes.
+fn pickup_menu(game: &Game) -> Option<Box<dyn State>> {
+    let position = game.level.entities[0].as_ref().unwrap().get_position();
+    let pile = game.level.items.get(&position).filter(|pile| pile.len() > 1)?;
+
+    let items = pile.iter().map(|item| item.describe_to(&game.level.discoveries)).collect();
+    Some(box ItemSelectState::new("Pick up what?", items, box |game, picked| {
+        game.level.pickup_choice = Some(picked);
+        game.level.command = Some(Command::PickUp);
+    }))
+}
+
+/// Lists what the player is carrying, if anything. Picking an item shows what can be done with it.
+fn inventory_menu(game: &Game) -> Option<Box<dyn State>> {
+    let player = game.level.entities[0].as_ref().unwrap();
+    if player.inventory.is_empty() {
+        return None
+    }
+
+    let title = format!("Inventory (weight {}/{})", player.carried_weight(), player.capacity());
+    let items = player.inventory.iter().map(|item| item.describe_to(&game.level.discoveries)).collect();
+    Some(box MenuState::new(&title, items, box |game, choice| {
+        item_menu(game, choice)
+    }))
+}
+
+/// What can be done with the item at `index` in the player's pack: using it,
+/// and naming its kind if it hasn't been identified. `None` if there's nothing to do.
+fn item_menu(game: &Game, index: usize) -> Option<Box<dyn State>> {
+    let item = &game.level.entities[0].as_ref().unwrap().inventory[index];
+    let verb = item.use_verb();
+    let unknown = !game.level.discoveries.is_known(&item.name);
+
+    let mut options = verb.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+    if unknown {
+        options.push("Call".to_string());
+    }
+    if options.is_empty() {
+        return None
+    }
+
+    let title = item.describe_to(&game.level.discoveries);
+    Some(box MenuState::new(&title, options, box move |game, choice| {
+        if verb.is_some() && choice == 0 {
+            use_item(game, index)
+        } else {
+            Some(call_prompt(game, index))
+        }
+    }))
+}
+
+/// Lists what the player is carrying that can be used, if anything
+fn use_menu(game: &Game) -> Option<Box<dyn State>> {
+    let player = game.level.entities[0].as_ref().unwrap();
+    let usable = (0..player.inventory.len()).filter(|&i| player.inventory[i].use_verb().is_some()).collect::<Vec<_>>();
+    if usable.is_empty() {
+        return None
+    }
+
+    let items = usable.iter().map(|&i| player.inventory[i].describe_to(&game.level.discoveries)).collect();
+    Some(box MenuState::new("Use what?", items, box move |game, choice| {
+        use_item(game, usable[choice])
+    }))
+}
+
+/// Uses the item at `index` in the player's pack, first asking where to zap it if it's a wand.
+/// Using it takes the player's turn once the menus close.
+fn use_item(game: &mut Game, index: usize) -> Option<Box<dyn State>> {
+    if game.level.entities[0].as_ref().unwrap().inventory[index].item_type == ItemType::WAND {
+        return Some(box TargetState::new("Zap where? (Enter to pick, Escape to cancel)", box move |game, at| {
+            game.level.use_choice = Some((index, Some(at)));
+            game.level.command = Some(Command::Use);
+        }))
+    }
+
+    game.level.use_choice = Some((index, None));
+    game.level.command = Some(Command::Use);
+    None
+}
+
+/// Asks what to call the kind of the unidentified item at `index` in the player's pack.
+/// A blank name forgets the last one.
+fn call_prompt(game: &Game, index: usize) -> Box<dyn State> {
+    let name = game.level.entities[0].as_ref().unwrap().inventory[index].name.clone();
+    let appearance = game.level.discoveries.appearance(&name).unwrap().to_string();
+
+    box TextInputState::new(&format!("Call the {} what?", appearance), MAX_CALLED_NAME, box move |game, called| {
+        game.level.discoveries.call(&name, &called);
+    })
+}
+
+impl TextInputState {
+    pub fn new(prompt: &str, max_len: usize, on_submit: TextInputCallback) -> TextInputState {
+        TextInputState { game: None, prompt: prompt.to_string(), input: String::new(), max_len, on_submit: Some(on_submit), done: false }
+    }
+}
+
+impl State for TextInputState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> { None }
+    fn maybe_exit_game(&self) -> Option<Exit> { None }
+    fn should_exit(&self) -> bool { self.done }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        let keypress = self.game.as_mut().unwrap().wait_for_keypress();
+        match keypress.code {
+            KeyCode::Escape => self.done = true,
+            KeyCode::Enter | KeyCode::NumPadEnter => {
+                let on_submit = self.on_submit.take().unwrap();
+                on_submit(self.game.as_mut().unwrap(), self.input.clone());
+                self.done = true;
+            }
+            KeyCode::Backspace => { self.input.pop(); }
+            _ => if keypress.printable != '\0' && !keypress.printable.is_control() && self.input.chars().count() < self.max_len {
+                self.input.push(keypress.printable);
+            }
+        }
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        game.rendering_component.before_render_new_frame();
+        game.render();
+        game.rendering_component.print(&format!("{} {}_", self.prompt, self.input), 0, MAP_OFFSET - 1);
+        game.rendering_component.after_render_new_frame();
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
+
+impl TargetState {
+    pub fn new(prompt: &str, on_target: TargetCallback) -> TargetState {
+        TargetState { game: None, prompt: prompt.to_string(), cursor: None, on_target: Some(on_target), done: false }
+    }
+}
+
+impl State for TargetState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> { None }
+    fn maybe_exit_game(&self) -> Option<Exit> { None }
+    fn should_exit(&self) -> bool { self.done }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        let cursor = self.cursor.unwrap_or(game.level.entities[0].as_ref().unwrap().get_position());
+
+        let keypress = game.wait_for_keypress();
+        match keypress.code {
+            KeyCode::Escape => self.done = true,
+            KeyCode::Enter | KeyCode::NumPadEnter => {
+                let on_target = self.on_target.take().unwrap();
+                on_target(game, cursor);
+                self.done = true;
+            }
+            _ => if let Some(Command::Move(direction)) = game.level.command {
+                let offset = direction.offset();
+                let moved = cursor.offset(offset.x, offset.y);
+                if game.level.map_component.contains(moved.x, moved.y) {
+                    self.cursor = Some(moved);
+                }
+            }
+        }
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        let cursor = self.cursor.unwrap_or(game.level.entities[0].as_ref().unwrap().get_position());
+
+        game.level.map_component.get_map_mut()[cursor.x as usize][cursor.y as usize].color_override = Some(Color { r: 255, g: 255, b: 0 });
+
+        game.rendering_component.before_render_new_frame();
+        game.render();
+        game.rendering_component.print(&self.prompt, 0, MAP_OFFSET - 1);
+        game.rendering_component.after_render_new_frame();
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
+
+impl DirectionState {
+    pub fn new(prompt: &str, on_direction: DirectionCallback) -> DirectionState {
+        DirectionState { game: None, prompt: prompt.to_string(), on_direction: Some(on_direction), done: false }
+    }
+}
+
+impl State for DirectionState {
+    fn maybe_new_state(&mut self) -> Option<Box<dyn State>> { None }
+    fn maybe_exit_game(&self) -> Option<Exit> { None }
+    fn should_exit(&self) -> bool { self.done }
+
+    fn exit(&mut self) -> Game { self.game.take().unwrap() }
+
+    fn update(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        let keypress = game.wait_for_keypress();
+        match (keypress.code, game.level.command) {
+            (KeyCode::Escape, _) => self.done = true,
+            (_, Some(Command::Move(direction))) if direction != Direction::NoDir => {
+                let on_direction = self.on_direction.take().unwrap();
+                on_direction(game, direction);
+                self.done = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&mut self) {
+        let game = self.game.as_mut().unwrap();
+        game.rendering_component.before_render_new_frame();
+        game.render();
+        game.rendering_component.print(&self.prompt, 0, MAP_OFFSET - 1);
+        game.rendering_component.after_render_new_frame();
+    }
+
+    fn get_game(&self) -> &Game { self.game.as_ref().unwrap() }
+    fn get_game_mut(&mut self) -> &mut Game { self.game.as_mut().unwrap() }
+
+    fn set_game(&mut self, game: Game) { self.game = Some(game) }
+}
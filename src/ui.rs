@@ -1,5 +1,6 @@
 use std::cmp::max;
 use std::iter::FromIterator;
+use std::ops::Range;
 
 const HORIZONTAL: &str = "━";
 const VERTICAL: &str = "┃";
@@ -7,6 +8,11 @@ const TOP_LEFT: &str = "┏";
 const TOP_RIGHT: &str = "┓";
 const BOTTOM_LEFT: &str = "┗";
 const BOTTOM_RIGHT: &str = "┛";
+/// How many options a menu shows at once, which keeps it on the screen and within the letters
+pub const PAGE_SIZE: usize = 20;
+/// The keys that turn to the previous and next page of a long menu
+const PREVIOUS_PAGE: char = '<';
+const NEXT_PAGE: char = '>';
 
 pub struct Menu {
     menu: Vec<String>,
@@ -15,25 +21,115 @@ pub struct Menu {
     height: u8,
 }
 
+impl Menu {
+    /// The lines to draw, border and all
+    pub fn lines(&self) -> &[String] {
+        &self.menu
+    }
+
+    /// How many options there are to choose from
+    pub fn num_options(&self) -> u8 {
+        self.num_options
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn height(&self) -> u8 {
+        self.height
+    }
+}
+
+/// Which page of a list of options a menu is showing. Lists longer than `PAGE_SIZE`
+/// are split into pages, each lettered from a, and turned with < and >.
+#[derive(Clone, Copy)]
+pub struct Pager {
+    page: usize,
+    count: usize,
+}
+
+impl Pager {
+    /// Starts on the first page of a list of `count` options
+    pub fn new(count: usize) -> Pager {
+        Pager { page: 0, count }
+    }
+
+    fn pages(&self) -> usize {
+        max(1, (self.count + PAGE_SIZE - 1) / PAGE_SIZE)
+    }
+
+    /// Where the options on the page showing are in the whole list
+    pub fn range(&self) -> Range<usize> {
+        self.page * PAGE_SIZE..(self.count.min((self.page + 1) * PAGE_SIZE))
+    }
+
+    /// The menu's title, with which page is showing if there's more than one
+    pub fn title(&self, title: &str) -> String {
+        if self.pages() == 1 {
+            return title.to_string()
+        }
+
+        format!("{} (page {} of {}, {} and {} to turn)", title, self.page + 1, self.pages(), PREVIOUS_PAGE, NEXT_PAGE)
+    }
+
+    /// The page showing as a menu, or `None` if there are no options
+    pub fn menu(&self, title: &str, options: &[String]) -> Option<Menu> {
+        menu(&self.title(title), options[self.range()].to_vec())
+    }
+
+    /// Where the option lettered `key` on the page showing is in the whole list
+    pub fn pick(&self, key: char) -> Option<usize> {
+        if !key.is_ascii_lowercase() {
+            return None
+        }
+
+        let i = self.range().start + (key as u8 - b'a') as usize;
+        if i < self.range().end { Some(i) } else { None }
+    }
+
+    /// Turns the page if `key` is < or >, returning whether it was
+    pub fn turn(&mut self, key: char) -> bool {
+        match key {
+            PREVIOUS_PAGE => self.page = self.page.saturating_sub(1),
+            NEXT_PAGE => self.page = (self.page + 1).min(self.pages() - 1),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// The letter an option is picked with, by its place on the page
+fn label(i: usize) -> char {
+    (b'a' + i as u8) as char
+}
+
+/// Builds a menu box with a title and a lettered line for each option.
+/// It takes no more than `PAGE_SIZE` options; longer lists are shown a page at a time with a `Pager`.
 pub fn menu(title: &str, options: Vec<String>) -> Option<Menu> {
     if options.len() == 0 { return None }
+    assert!(options.len() <= PAGE_SIZE, "a menu with more options than fit on a page needs a Pager");
 
-    let options_max = options.iter().fold(&options[0], |acc, x| {
-        if x.len() > acc.len() { return x }
-        acc
-    }).len();
+    let lines = options.iter().enumerate()
+        .map(|(i, option)| format!("({}) {}", label(i), option))
+        .collect::<Vec<_>>();
+    Some(boxed(title, lines, options.len() as u8))
+}
 
-    // 8 is 2 box characters + 2 padding characters + "(x) "
-    let width = max(title.len(), options_max) + 8;
-    // Plus 3 for title and bounds
-    let height = options.len() + 3;
+/// Builds a box with a title and lines that can't be picked, such as an empty list
+pub fn text_box(title: &str, lines: Vec<String>) -> Menu {
+    boxed(title, lines, 0)
+}
 
-    let mut menu = vec![String::new(); options.len()];
+fn boxed(title: &str, contents: Vec<String>, num_options: u8) -> Menu {
+    let contents_max = contents.iter().map(|line| line.len()).max().unwrap_or(0);
 
-    for (i, line) in menu.iter_mut().enumerate() {
-        let content = format!("({}) {}", /* this is an ugly hack */ ((i+97) as u8) as char, options[i]);
-        *line = format!("┃ {:<1$} ┃", content, width-4);
-    }
+    // 4 is 2 box characters + 2 padding characters
+    let width = max(title.len(), contents_max) + 4;
+    // Plus 3 for title and bounds
+    let height = contents.len() + 3;
+
+    let mut menu = contents.iter().map(|line| format!("{0} {1:<2$} {0}", VERTICAL, line, width-4)).collect::<Vec<_>>();
 
     let mut top_line = vec![HORIZONTAL; width];
     top_line[0] = TOP_LEFT;
@@ -41,7 +137,7 @@ pub fn menu(title: &str, options: Vec<String>) -> Option<Menu> {
     let top_line_len = top_line.len()-1;
     top_line[top_line_len] = TOP_RIGHT;
 
-    let title_line = format!("┃ {:<1$} ┃", title, width-4);
+    let title_line = format!("{0} {1:<2$} {0}", VERTICAL, title, width-4);
 
     let mut top_line_str = String::from_iter(top_line.clone());
 
@@ -54,10 +150,10 @@ pub fn menu(title: &str, options: Vec<String>) -> Option<Menu> {
 
     menu.push(top_line_str);
 
-    Some(Menu {
+    Menu {
             menu,
-            num_options: options.len() as u8,
+            num_options,
             width: width as u8,
             height: height as u8,
-    })
-}
\ No newline at end of file
+    }
+}
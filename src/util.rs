@@ -90,6 +90,36 @@ impl Point {
         (absdiff(self.x, other.x) + absdiff(self.y, other.y)) as u32
     }
 
+    /// Whether another point is one of the eight points surrounding the current one
+    pub fn is_adjacent(&self, other: &Point) -> bool {
+        *self != *other && absdiff(self.x, other.x) <= 1 && absdiff(self.y, other.y) <= 1
+    }
+
+    /// The points on a straight line from the current point to another, both ends included.
+    pub fn line_to(&self, other: &Point) -> Vec<Point> {
+        // Bresenham's line algorithm
+        let (dx, dy) = (absdiff(self.x, other.x), -absdiff(self.y, other.y));
+        let (sx, sy) = (if self.x < other.x { 1 } else { -1 }, if self.y < other.y { 1 } else { -1 });
+        let mut err = dx + dy;
+        let mut current = *self;
+        let mut line = vec![current];
+
+        while current != *other {
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                current.x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                current.y += sy;
+            }
+            line.push(current);
+        }
+
+        line
+    }
+
     /// Determine which points adjoining the current point can be used
     /// as a successor for the current point in an A* algorithm.
     pub fn successors(&self, map: &Box<dyn MapComponent>, goal: &Point) -> Vec<(Point, u32)> {
@@ -98,10 +128,11 @@ impl Point {
         for (idx, i) in [(0, 1), (0, -1), (1, 0), (-1, 0), (-1, -1), (1, 1), (1, -1), (-1, 1)].iter().enumerate() {
             let p = Point { x: x + i.0, y: y + i.1 };
             if (!map.is_blocked(x + i.0, y + i.1) && !map.is_occupied(x + i.0, y + i.1)) || p == *goal {
+                let cost = map.move_cost(p.x, p.y);
                 if idx > 3 {
-                    successors.push((p, 14));
+                    successors.push((p, cost * 14 / 10));
                 } else {
-                    successors.push((p, 10));
+                    successors.push((p, cost));
                 }
             }
         }
@@ -140,3 +171,26 @@ pub fn add_punctuation(mut s: String) -> String {
     s.push('.');
     s
 }
+
+/// Upper-cases the first letter of a message
+pub fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Lower-cases the first letter of a name, for the middle of a message
+pub fn uncapitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// 64 bit FNV-1a, which is plenty to tell files apart or spot a garbled one
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3))
+}
@@ -0,0 +1,173 @@
+use crate::game::Game;
+use crate::state::{State, MenuState, TargetState};
+use crate::util::Point;
+use crate::actor::{Actor, Alertness};
+use crate::item;
+
+/// The deepest level the wizard menu offers to jump to
+const MAX_DEPTH: u32 = 10;
+
+/// What the wizard key opens: the wizard menu, after asking to turn wizard mode on if it's off
+pub fn open(game: &Game) -> Box<dyn State> {
+    with_wizard(game, menu)
+}
+
+/// Opens the state made by `then`, first asking to turn wizard mode on if it's off
+pub fn with_wizard(game: &Game, then: fn() -> Box<dyn State>) -> Box<dyn State> {
+    if game.settings.wizard {
+        return then()
+    }
+
+    let options = vec!["Yes".to_string(), "No".to_string()];
+    box MenuState::new("Become a wizard? This game won't count for the high score", options, box move |game, choice| {
+        if choice != 0 {
+            return None
+        }
+
+        game.enable_wizard();
+        game.game_log("You feel knowledgeable".to_string());
+        Some(then())
+    })
+}
+
+fn menu() -> Box<dyn State> {
+    let options = ["Toggle map reveal", "Toggle AI paths", "Spawn a monster", "Create an item", "Teleport", "Heal", "Go to depth"];
+
+    box MenuState::new("Wizard", options.iter().map(|o| o.to_string()).collect(), box |game, choice| {
+        match choice {
+            0 => game.toggle_show_map(),
+            1 => game.toggle_show_ai(),
+            2 => return Some(monster_menu(game)),
+            3 => return Some(item_menu()),
+            4 => return Some(box TargetState::new("Teleport where? (Enter to pick, Escape to cancel)", box |game, to| {
+                run(game, |game| teleport(game, to));
+            })),
+            5 => run(game, heal),
+            6 => return Some(depth_menu()),
+            _ => {}
+        }
+
+        None
+    })
+}
+
+fn monster_menu(game: &Game) -> Box<dyn State> {
+    let names = game.monsters.names();
+
+    box MenuState::new("Spawn which monster?", names.clone(), box move |game, choice| {
+        let near = game.level.entities[0].as_ref().unwrap().get_position();
+        run(game, |game| spawn_monster(game, &names[choice], near));
+        None
+    })
+}
+
+fn item_menu() -> Box<dyn State> {
+    let names = item::catalogue().iter().map(|t| t.name.to_string()).collect::<Vec<_>>();
+
+    box MenuState::new("Create which item?", names.clone(), box move |game, choice| {
+        run(game, |game| create_item(game, &names[choice]));
+        None
+    })
+}
+
+fn depth_menu() -> Box<dyn State> {
+    let depths = (1..MAX_DEPTH + 1).map(|d| format!("Depth {}", d)).collect();
+
+    box MenuState::new("Go to which depth?", depths, box |game, choice| {
+        run(game, |game| goto_depth(game, choice as u32 + 1));
+        None
+    })
+}
+
+/// Runs a wizard command and logs what came of it, good or bad
+fn run(game: &mut Game, command: impl FnOnce(&mut Game) -> Result<String, String>) {
+    match command(game) {
+        Ok(message) | Err(message) => game.game_log(message),
+    }
+}
+
+/// Spawns the monster called `name` on the closest free tile to `near`
+pub fn spawn_monster(game: &mut Game, name: &str, near: Point) -> Result<String, String> {
+    let at = game.level.free_point_near(near).ok_or("There's no room for it".to_string())?;
+    spawn_monster_at(game, name, at)
+}
+
+/// Spawns the monster called `name` exactly at `at`
+pub fn spawn_monster_at(game: &mut Game, name: &str, at: Point) -> Result<String, String> {
+    let template = game.monsters.find(name).ok_or(format!("There's no such monster as a {}", name))?.clone();
+    if !is_free(game, at) {
+        return Err(format!("There's no room for a {} there", name))
+    }
+
+    let mut monster = template.spawn(at.x, at.y);
+    monster.alertness = Alertness::Wandering;
+    game.level.spawn(monster);
+
+    Ok(format!("A {} appears", template.name))
+}
+
+/// Creates the item called `name` at the player's feet
+pub fn create_item(game: &mut Game, name: &str) -> Result<String, String> {
+    let template = item::find(name).ok_or(format!("There's no such item as a {}", name))?;
+    let at = game.level.entities[0].as_ref().unwrap().get_position();
+
+    item::add_to_pile(game.level.items.entry(at).or_insert_with(Vec::new), template.spawn(at));
+    Ok(format!("A {} appears at your feet", template.name))
+}
+
+/// Puts `quantity` of the item called `name` straight into the player's pack.
+/// Items that don't stack are given one at a time.
+pub fn give_item(game: &mut Game, name: &str, quantity: u32) -> Result<String, String> {
+    let template = item::find(name).ok_or(format!("There's no such item as a {}", name))?;
+    if quantity == 0 {
+        return Err("You can't give nothing".to_string())
+    }
+    let player = game.level.entities[0].as_mut().unwrap();
+    let position = player.get_position();
+
+    if template.stackable {
+        player.stow(template.spawn_stack(position, quantity));
+    } else {
+        for _ in 0..quantity {
+            player.inventory.push(template.spawn(position));
+        }
+    }
+
+    Ok(match quantity {
+        1 => format!("A {} appears in your pack", template.name),
+        _ => format!("{} appear in your pack", template.spawn_stack(position, quantity).describe()),
+    })
+}
+
+/// Moves the player straight to `to`
+pub fn teleport(game: &mut Game, to: Point) -> Result<String, String> {
+    if !is_free(game, to) {
+        return Err("You can't teleport there".to_string())
+    }
+
+    game.level.teleport(0, to);
+    Ok("You teleport".to_string())
+}
+
+/// Whether `point` is on the map, walkable, and not stood on
+fn is_free(game: &Game, point: Point) -> bool {
+    let map = &game.level.map_component;
+    map.contains(point.x, point.y) && !map.is_blocked(point.x, point.y) && !map.is_occupied(point.x, point.y)
+}
+
+/// Restores the player to full health
+pub fn heal(game: &mut Game) -> Result<String, String> {
+    let player = game.level.entities[0].as_mut().unwrap();
+    player.health = player.max_health;
+    Ok("You feel much better".to_string())
+}
+
+/// Moves the player to a new level at `depth`
+pub fn goto_depth(game: &mut Game, depth: u32) -> Result<String, String> {
+    if depth < 1 {
+        return Err("There's nothing above the first level".to_string())
+    }
+
+    game.goto_depth(depth)?;
+    Ok(format!("You are now on depth {}", depth))
+}